@@ -0,0 +1,207 @@
+//! Steady-state confidence intervals from a single run by the method of
+//! batch means.
+
+use crate::{estimation::Estimate, samples::Samples, time_weighted::TimeWeighted};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Number of batches a sequence of observations is first split into. A power
+/// of two, so that batches can be merged pairwise down to [`MIN_BATCHES`].
+const INITIAL_BATCHES: usize = 1024;
+
+/// Fewest batches left after merging. Below this the t interval gets too wide
+/// to be useful.
+const MIN_BATCHES: usize = 10;
+
+/// Critical value of the two-sided 5% test of the lag-1 autocorrelation, which
+/// is approximately normal with variance `1 / batches` for independent means.
+const CRITICAL_VALUE: f64 = 1.96;
+
+/// Steady-state estimate of a mean from a single long run by the method of
+/// batch means.
+///
+/// The run is split into many equal batches, which are merged pairwise until
+/// the lag-1 autocorrelation of their means is no longer significant or too
+/// few batches are left. The batch means are then treated as independent
+/// samples of the mean.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchMeans {
+    /// Estimate over the batch means; its `count` is the number of batches.
+    pub estimate: Estimate,
+    /// Observations per batch, or seconds per batch for time-weighted series.
+    pub batch_size: f64,
+    /// Lag-1 autocorrelation of the batch means that were kept.
+    pub lag1_autocorrelation: f64,
+    /// Whether the batch means passed the autocorrelation test. When not, the
+    /// interval is likely too narrow and the run should be longer.
+    pub independent: bool,
+}
+
+impl BatchMeans {
+    /// Estimates the mean of a sequence of observations, e.g. wait times in the
+    /// order customers started service. Leading observations that do not fill
+    /// a batch are dropped. `None` with too few observations.
+    pub fn from_samples(samples: &[f64], confidence: f64) -> Option<Self> {
+        let batches = (samples.len().min(INITIAL_BATCHES) + 1).next_power_of_two() / 2;
+        if batches < MIN_BATCHES {
+            return None;
+        }
+        let batch_size = samples.len() / batches;
+        let means = samples[samples.len() - batches * batch_size..]
+            .chunks(batch_size)
+            .map(|batch| batch.iter().sum::<f64>() / batch_size as f64)
+            .collect();
+        Some(Self::from_batches(means, batch_size as f64, confidence))
+    }
+
+    /// Estimates the mean of the observations recorded by `samples`, over the
+    /// batches it keeps. `None` when fewer than 10 batches are complete.
+    pub fn from_batched_samples(samples: &Samples, confidence: f64) -> Option<Self> {
+        let (means, batch_size) = samples.batch_means();
+        if means.len() < MIN_BATCHES {
+            return None;
+        }
+        Some(Self::from_batches(means, batch_size as f64, confidence))
+    }
+
+    /// Estimates the time-weighted mean of a quantity from its start until
+    /// `end`, over the batches kept by the accumulator. `None` when fewer than
+    /// 10 batches are complete.
+    pub fn from_time_weighted(
+        time_weighted: &TimeWeighted,
+        end: Duration,
+        confidence: f64,
+    ) -> Option<Self> {
+        let (means, width) = time_weighted.batch_means(end);
+        if means.len() < MIN_BATCHES {
+            return None;
+        }
+        Some(Self::from_batches(means, width, confidence))
+    }
+
+    /// Merges equally sized batches pairwise until their means pass the
+    /// autocorrelation test.
+    fn from_batches(mut means: Vec<f64>, mut batch_size: f64, confidence: f64) -> Self {
+        loop {
+            let lag1_autocorrelation = lag1_autocorrelation(&means);
+            let independent =
+                lag1_autocorrelation.abs() <= CRITICAL_VALUE / (means.len() as f64).sqrt();
+            if independent || means.len() / 2 < MIN_BATCHES {
+                return Self {
+                    estimate: Estimate::from_samples(&means, confidence),
+                    batch_size,
+                    lag1_autocorrelation,
+                    independent,
+                };
+            }
+            means = means
+                .chunks_exact(2)
+                .map(|pair| (pair[0] + pair[1]) / 2.0)
+                .collect();
+            batch_size *= 2.0;
+        }
+    }
+}
+
+/// Sample lag-1 autocorrelation, zero for a constant sequence.
+fn lag1_autocorrelation(values: &[f64]) -> f64 {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    let covariance = values
+        .windows(2)
+        .map(|pair| (pair[0] - mean) * (pair[1] - mean))
+        .sum::<f64>();
+    covariance / variance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::SimulationRng;
+    use rand::{Rng, SeedableRng};
+
+    fn uniform(count: usize) -> Vec<f64> {
+        let mut rng = SimulationRng::seed_from_u64(7);
+        (0..count).map(|_| rng.random::<f64>()).collect()
+    }
+
+    #[test]
+    fn independent_samples_pass_the_autocorrelation_test() {
+        let estimate = BatchMeans::from_samples(&uniform(10_000), 0.95).unwrap();
+        assert!(estimate.independent);
+        assert_eq!(estimate.estimate.count, 1024);
+        assert_eq!(estimate.batch_size, 9.0);
+        assert!((estimate.estimate.mean - 0.5).abs() < estimate.estimate.half_width);
+    }
+
+    #[test]
+    fn correlated_samples_fail_the_autocorrelation_test() {
+        let trend = (0..1000).map(f64::from).collect::<Vec<_>>();
+        let estimate = BatchMeans::from_samples(&trend, 0.95).unwrap();
+        assert!(!estimate.independent);
+        assert!(estimate.estimate.count >= MIN_BATCHES);
+    }
+
+    #[test]
+    fn too_few_samples_give_no_estimate() {
+        assert!(BatchMeans::from_samples(&uniform(9), 0.95).is_none());
+        assert!(BatchMeans::from_samples(&uniform(15), 0.95).is_none());
+        assert_eq!(
+            BatchMeans::from_samples(&uniform(16), 0.95)
+                .unwrap()
+                .estimate
+                .count,
+            16
+        );
+    }
+
+    #[test]
+    fn batched_samples_work_with_any_estimator() {
+        let mut samples = Samples::p2();
+        for value in uniform(10_000) {
+            samples.push(value);
+        }
+        let estimate = BatchMeans::from_batched_samples(&samples, 0.95).unwrap();
+        assert!(estimate.independent);
+        assert!((estimate.estimate.mean - 0.5).abs() < estimate.estimate.half_width);
+
+        let mut samples = Samples::histogram(0.0, 0.1, 10);
+        for value in uniform(9) {
+            samples.push(value);
+        }
+        assert!(BatchMeans::from_batched_samples(&samples, 0.95).is_none());
+    }
+
+    #[test]
+    fn independent_levels_pass_the_autocorrelation_test() {
+        let mut time_weighted = TimeWeighted::new(0.0, Duration::ZERO);
+        for (second, value) in uniform(1000).into_iter().enumerate() {
+            time_weighted.set(value, Duration::from_secs(second as u64));
+        }
+        let estimate =
+            BatchMeans::from_time_weighted(&time_weighted, Duration::from_secs(1000), 0.95)
+                .unwrap();
+        assert!(estimate.independent);
+        assert!((estimate.estimate.mean - 0.5).abs() < estimate.estimate.half_width);
+    }
+
+    #[test]
+    fn too_short_a_time_gives_no_estimate() {
+        let time_weighted = TimeWeighted::new(1.0, Duration::ZERO);
+        assert!(
+            BatchMeans::from_time_weighted(&time_weighted, Duration::from_micros(9_500), 0.95)
+                .is_none()
+        );
+        let estimate =
+            BatchMeans::from_time_weighted(&time_weighted, Duration::from_millis(10), 0.95)
+                .unwrap();
+        assert_eq!(estimate.estimate.count, 10);
+        assert_eq!(estimate.estimate.mean, 1.0);
+    }
+}
@@ -1,28 +1,47 @@
 use crate::{
-    blocks::{Block, BlockId},
-    events::{Event, EventType},
+    blocks::{Block, BlockId, BlockKind, BlockStats, BlockStepStats},
+    entity::{Entities, Entity, Value},
+    error::{SimulationError, ValidationError},
+    events::{Event, EventQueue, EventType},
+    random::{stream_seed, unseeded_rng, SimulationRng},
     routers::Router,
-    stats::{Stats, StepStats},
-};
-use rand::{distr::Distribution, rng, Rng};
-use std::{
-    collections::{BinaryHeap, HashMap},
-    fmt::Debug,
-    time::Duration,
+    stats::{Metrics, Stats, StepStats},
 };
+use rand::{distr::Distribution, Rng, SeedableRng};
+use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
+
+/// Callback completing every entity created by a [`CreateBlock`], e.g. with
+/// attributes drawn from the random stream of the block.
+pub type OnCreate = Box<dyn FnMut(&mut Entity, &mut SimulationRng) + Send>;
+
+/// Kind, priority and attributes given to every created entity.
+#[derive(Default)]
+struct EntityTemplate {
+    kind: Option<String>,
+    priority: i32,
+    attributes: HashMap<String, Value>,
+    on_create: Option<OnCreate>,
+}
 
+/// Builder of a [`CreateBlock`]. The inter-arrival distribution and the
+/// router must be set before it can be built.
 pub struct CreateBlockBuilder<Distribution, Router> {
     id: BlockId,
-    first_at: (usize, Duration),
+    first_at: Duration,
+    template: EntityTemplate,
     router: Router,
     distribution: Distribution,
 }
 
 impl<R> CreateBlockBuilder<(), R> {
+    /// Sets the distribution of the time between created entities, in
+    /// seconds.
     pub fn distribution<D: Distribution<f32>>(self, distribution: D) -> CreateBlockBuilder<D, R> {
         CreateBlockBuilder {
             id: self.id,
             first_at: self.first_at,
+            template: self.template,
             router: self.router,
             distribution,
         }
@@ -30,10 +49,12 @@ impl<R> CreateBlockBuilder<(), R> {
 }
 
 impl<D> CreateBlockBuilder<D, ()> {
+    /// Sets the router choosing the block every created entity enters.
     pub fn router<R: Router>(self, router: R) -> CreateBlockBuilder<D, R> {
         CreateBlockBuilder {
             id: self.id,
             first_at: self.first_at,
+            template: self.template,
             distribution: self.distribution,
             router,
         }
@@ -41,42 +62,88 @@ impl<D> CreateBlockBuilder<D, ()> {
 }
 
 impl<D, R> CreateBlockBuilder<D, R> {
-    pub fn first_at(mut self, first_at: (usize, Duration)) -> CreateBlockBuilder<D, R> {
+    /// Sets the time of the first created entity.
+    pub fn first_at(mut self, first_at: Duration) -> CreateBlockBuilder<D, R> {
         self.first_at = first_at;
         self
     }
+
+    /// Sets the kind of created entities.
+    pub fn kind(mut self, kind: impl Into<String>) -> Self {
+        self.template.kind = Some(kind.into());
+        self
+    }
+
+    /// Sets the priority class of created entities, 0 by default.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.template.priority = priority;
+        self
+    }
+
+    /// Sets an attribute of created entities.
+    pub fn attribute(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.template.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets a callback run on every created entity after its kind, priority
+    /// and attributes are set. The callback draws from its own random stream,
+    /// leaving inter-arrival times unchanged.
+    pub fn on_create(
+        mut self,
+        on_create: impl FnMut(&mut Entity, &mut SimulationRng) + Send + 'static,
+    ) -> Self {
+        self.template.on_create = Some(Box::new(on_create));
+        self
+    }
 }
 
 impl<D: Distribution<f32>, R: Router> CreateBlockBuilder<D, R> {
+    /// Builds the block, creating its first entity at time zero unless set
+    /// otherwise by [`CreateBlockBuilder::first_at`].
     pub fn build(self) -> CreateBlock<D, R> {
         CreateBlock {
             id: self.id,
             first_at: self.first_at,
+            template: self.template,
             created_events: 0,
             router: self.router,
             distribution: self.distribution,
+            rng: unseeded_rng(),
+            attributes_rng: unseeded_rng(),
         }
     }
 }
 
-#[derive(Debug)]
+/// Statistics of a [`CreateBlock`], both over the simulation and at every
+/// step.
+#[derive(Debug, Clone, Serialize)]
 pub struct CreateBlockStats {
+    /// Entities created so far.
     pub created_events: usize,
 }
 
+/// Source block creating entities with inter-arrival times drawn from `D`.
 pub struct CreateBlock<D, R> {
+    /// Name of the block, unique within its network.
     pub id: BlockId,
+    /// Entities created so far.
     pub created_events: usize,
     router: R,
-    first_at: (usize, Duration),
+    first_at: Duration,
+    template: EntityTemplate,
     distribution: D,
+    rng: SimulationRng,
+    attributes_rng: SimulationRng,
 }
 
 impl CreateBlock<(), ()> {
+    /// Starts building a create block named `id`.
     pub fn builder(id: BlockId) -> CreateBlockBuilder<(), ()> {
         CreateBlockBuilder {
             id,
-            first_at: (0, Duration::ZERO),
+            first_at: Duration::ZERO,
+            template: EntityTemplate::default(),
             router: (),
             distribution: (),
         }
@@ -84,55 +151,113 @@ impl CreateBlock<(), ()> {
 }
 
 impl<D: Distribution<f32>, R: Router> CreateBlock<D, R> {
-    fn delay(&self) -> Duration {
-        Duration::from_secs_f32(rng().sample(&self.distribution))
+    fn delay(&mut self) -> Result<Duration, SimulationError> {
+        let value = self.rng.sample(&self.distribution);
+        Duration::try_from_secs_f32(value).map_err(|_| SimulationError::InvalidDelay {
+            block: self.id,
+            value,
+        })
+    }
+
+    /// Creates the entity arriving at `created_at` and schedules its departure
+    /// from this block.
+    fn create(
+        &mut self,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+        created_at: Duration,
+    ) {
+        let entity = entities.create(created_at);
+        entity.kind.clone_from(&self.template.kind);
+        entity.priority = self.template.priority;
+        entity.attributes.clone_from(&self.template.attributes);
+        if let Some(on_create) = &mut self.template.on_create {
+            on_create(entity, &mut self.attributes_rng);
+        }
+        event_queue.push(Event(created_at, self.id, EventType::Out, entity.id));
     }
 }
 
 impl<D: Distribution<f32>, R: Router> Stats for CreateBlock<D, R> {
-    fn stats(&self) -> Box<dyn Debug> {
-        Box::new(CreateBlockStats {
+    type Output = BlockStats;
+
+    fn stats(&self) -> BlockStats {
+        BlockStats::Create(CreateBlockStats {
             created_events: self.created_events,
         })
     }
 }
 
 impl<D: Distribution<f32>, R: Router> StepStats for CreateBlock<D, R> {
-    fn step_stats(&self) -> Box<dyn Debug> {
-        self.stats()
+    type Output = BlockStepStats;
+
+    fn step_stats(&self) -> BlockStepStats {
+        BlockStepStats::Create(CreateBlockStats {
+            created_events: self.created_events,
+        })
     }
 }
 
-impl<D: Distribution<f32>, R: Router> Block for CreateBlock<D, R> {
+impl Metrics for CreateBlockStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        vec![("created_events".into(), self.created_events as f64)]
+    }
+}
+
+impl<D: Distribution<f32> + Send, R: Router> Block for CreateBlock<D, R> {
     fn id(&self) -> BlockId {
         self.id
     }
 
-    fn next(&self, blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId> {
-        self.router.next(blocks)
+    fn kind(&self) -> BlockKind {
+        BlockKind::Create
     }
 
-    fn init(&mut self, event_queue: &mut BinaryHeap<Event>) {
-        event_queue.push(Event(
-            self.first_at.1,
-            self.id,
-            EventType::Out,
-            self.first_at.0,
-        ));
+    fn targets(&self) -> Vec<BlockId> {
+        self.router.targets()
+    }
+
+    fn validate(&self, errors: &mut Vec<ValidationError>) {
+        self.router.validate(self.id, errors);
+    }
+
+    fn next(
+        &mut self,
+        entity: &mut Entity,
+        blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
+        self.router.next(entity, blocks)
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = SimulationRng::seed_from_u64(seed);
+        self.attributes_rng = SimulationRng::seed_from_u64(stream_seed(seed, "attributes"));
+        self.router.seed(stream_seed(seed, "router"));
+    }
+
+    fn init(
+        &mut self,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+    ) -> Result<(), SimulationError> {
+        self.create(entities, event_queue, self.first_at);
+        Ok(())
+    }
+
+    fn reset_stats(&mut self, _simulation_duration: Duration) {
+        self.created_events = 0;
     }
 
     fn process_out(
         &mut self,
-        event_id: usize,
-        event_queue: &mut BinaryHeap<Event>,
+        _entity_id: usize,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
         simulation_duration: Duration,
-    ) {
-        event_queue.push(Event(
-            simulation_duration + self.delay(),
-            self.id,
-            EventType::Out,
-            event_id + 1,
-        ));
+    ) -> Result<(), SimulationError> {
+        let created_at = simulation_duration + self.delay()?;
+        self.create(entities, event_queue, created_at);
         self.created_events += 1;
+        Ok(())
     }
 }
@@ -1,44 +1,92 @@
 use crate::{
-    blocks::{Block, BlockId},
-    events::Event,
-    stats::{Stats, StepStats},
-};
-use std::{
-    collections::{BinaryHeap, HashMap},
-    fmt::Debug,
-    time::Duration,
+    batch_means::BatchMeans,
+    blocks::{Block, BlockId, BlockKind, BlockStats, BlockStepStats},
+    entity::{Entities, Entity},
+    error::SimulationError,
+    events::EventQueue,
+    samples::{SampleStats, Samples},
+    stats::{prefixed, Metrics, Stats, StepStats},
 };
+use serde::Serialize;
+use std::{collections::HashMap, time::Duration};
 
-#[derive(Debug)]
+/// Statistics of a [`DisposeBlock`] over the whole simulation.
+#[derive(Debug, Clone, Serialize)]
 pub struct DisposeBlockStats {
+    /// Entities disposed of so far.
+    pub disposed_events: usize,
+    /// Time disposed entities spent in the network since their creation.
+    pub system_time: SampleStats,
+}
+
+/// Instantaneous state of a [`DisposeBlock`].
+#[derive(Debug, Clone, Serialize)]
+pub struct DisposeBlockStepStats {
+    /// Entities disposed of so far.
     pub disposed_events: usize,
 }
 
+/// Sink block removing entities from the network and recording how long they
+/// spent in it.
 pub struct DisposeBlock {
+    /// Name of the block, unique within its network.
     pub id: BlockId,
+    /// Entities disposed of so far.
     pub disposed_events: usize,
+    /// Time in system of every disposed entity, in seconds.
+    pub system_times: Samples,
 }
 
 impl DisposeBlock {
+    /// Creates a dispose block named `id`, summarizing times in system with
+    /// the default [`Samples`] estimator.
     pub fn new(id: BlockId) -> Self {
         Self {
             id,
             disposed_events: 0,
+            system_times: Samples::new(),
         }
     }
+
+    /// Sets how times in system are summarized, by P² by default.
+    pub fn system_times(mut self, system_times: Samples) -> Self {
+        self.system_times = system_times;
+        self
+    }
 }
 
 impl Stats for DisposeBlock {
-    fn stats(&self) -> Box<dyn Debug> {
-        Box::new(DisposeBlockStats {
+    type Output = BlockStats;
+
+    fn stats(&self) -> BlockStats {
+        BlockStats::Dispose(DisposeBlockStats {
             disposed_events: self.disposed_events,
+            system_time: self.system_times.stats(),
         })
     }
 }
 
 impl StepStats for DisposeBlock {
-    fn step_stats(&self) -> Box<dyn Debug> {
-        self.stats()
+    type Output = BlockStepStats;
+
+    fn step_stats(&self) -> BlockStepStats {
+        BlockStepStats::Dispose(DisposeBlockStepStats {
+            disposed_events: self.disposed_events,
+        })
+    }
+}
+
+impl Metrics for DisposeBlockStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![("disposed_events".into(), self.disposed_events as f64)];
+        metrics.extend(prefixed("system_time", &self.system_time));
+        metrics
+    }
+}
+
+impl Metrics for DisposeBlockStepStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        vec![("disposed_events".into(), self.disposed_events as f64)]
     }
 }
 
@@ -47,16 +95,51 @@ impl Block for DisposeBlock {
         self.id
     }
 
-    fn next(&self, _blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId> {
+    fn kind(&self) -> BlockKind {
+        BlockKind::Dispose
+    }
+
+    fn targets(&self) -> Vec<BlockId> {
+        Vec::new()
+    }
+
+    fn next(
+        &mut self,
+        _entity: &mut Entity,
+        _blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
         None
     }
 
+    fn batch_means(
+        &self,
+        _simulation_duration: Duration,
+        confidence: f64,
+    ) -> Vec<(String, BatchMeans)> {
+        BatchMeans::from_batched_samples(&self.system_times, confidence)
+            .map(|estimate| ("system_time".to_string(), estimate))
+            .into_iter()
+            .collect()
+    }
+
+    fn reset_stats(&mut self, _simulation_duration: Duration) {
+        self.disposed_events = 0;
+        self.system_times.clear();
+    }
+
     fn process_in(
         &mut self,
-        _event_id: usize,
-        _event_queue: &mut BinaryHeap<Event>,
-        _simulation_duration: Duration,
-    ) {
+        entity_id: usize,
+        entities: &mut Entities,
+        _event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        let entity = entities
+            .remove(entity_id)
+            .ok_or(SimulationError::UnknownEntity(entity_id))?;
         self.disposed_events += 1;
+        self.system_times
+            .push((simulation_duration - entity.created_at).as_secs_f64());
+        Ok(())
     }
 }
@@ -1,38 +1,262 @@
+//! Building blocks of a network: sources creating entities, servers with
+//! queues and sinks disposing of them.
+
 mod create;
 mod dispose;
 mod process;
 
-pub use create::CreateBlock;
-pub use dispose::DisposeBlock;
-pub use process::ProcessBlock;
+pub use create::{CreateBlock, CreateBlockBuilder, CreateBlockStats};
+pub use dispose::{DisposeBlock, DisposeBlockStats, DisposeBlockStepStats};
+pub use process::{
+    Balking, ClassStats, Patience, Preemption, ProcessBlock, ProcessBlockBuilder,
+    ProcessBlockStats, ProcessBlockStepStats,
+};
 
 use crate::{
-    events::Event,
-    stats::{Stats, StepStats},
+    batch_means::BatchMeans,
+    entity::{Entities, Entity},
+    error::{SimulationError, ValidationError},
+    events::EventQueue,
+    queue::QueueEntry,
+    stats::{Metrics, Stats, StepStats},
 };
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeMap, HashMap},
     time::Duration,
 };
 
+/// Unique name of a block within a network.
 pub type BlockId = &'static str;
 
-pub trait Block: Stats + StepStats {
+/// Role of a block in the network topology.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockKind {
+    /// Source of entities, where validation starts its search for
+    /// unreachable blocks.
+    Create,
+    /// Block serving entities, which must lead to a dispose block.
+    Process,
+    /// Sink of entities.
+    Dispose,
+    /// Any other block, e.g. one defined outside of this crate.
+    Other,
+}
+
+/// Statistics of any block, collected over the whole simulation.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockStats {
+    /// Statistics of a [`CreateBlock`].
+    Create(CreateBlockStats),
+    /// Statistics of a [`ProcessBlock`].
+    Process(Box<ProcessBlockStats>),
+    /// Statistics of a [`DisposeBlock`].
+    Dispose(DisposeBlockStats),
+    /// Named values reported by blocks outside of this crate.
+    Other(BTreeMap<String, f64>),
+}
+
+/// Instantaneous state of any block.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockStepStats {
+    /// State of a [`CreateBlock`].
+    Create(CreateBlockStats),
+    /// State of a [`ProcessBlock`].
+    Process(ProcessBlockStepStats),
+    /// State of a [`DisposeBlock`].
+    Dispose(DisposeBlockStepStats),
+    /// Named values reported by blocks outside of this crate.
+    Other(BTreeMap<String, f64>),
+}
+
+impl Metrics for BlockStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        match self {
+            Self::Create(stats) => stats.metrics(),
+            Self::Process(stats) => stats.metrics(),
+            Self::Dispose(stats) => stats.metrics(),
+            Self::Other(metrics) => metrics.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl Metrics for BlockStepStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        match self {
+            Self::Create(stats) => stats.metrics(),
+            Self::Process(stats) => stats.metrics(),
+            Self::Dispose(stats) => stats.metrics(),
+            Self::Other(metrics) => metrics.clone().into_iter().collect(),
+        }
+    }
+}
+
+/// What a block does with an entity whose next block is full when its
+/// service ends. Except when rejecting, entities leaving the block enter
+/// their next block at once rather than through an `In` event, so that no
+/// other arrival can take the room they were checked against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Blocking {
+    /// The entity moves on and is rejected by the next block.
+    #[default]
+    Reject,
+    /// The entity keeps its device blocked until the next block has room.
+    AfterService,
+    /// Like `AfterService`, and besides, services only start while every
+    /// block the router can send to has room. An entity that cannot start
+    /// keeps its device blocked until they do.
+    BeforeService,
+}
+
+/// An entity switching queues within a jockeying group, see
+/// [`QueueNetwork::jockeying`](crate::network::QueueNetwork::jockeying).
+#[derive(Debug, Clone, Copy)]
+pub struct Jockey {
+    /// The entity's place in the queue it leaves.
+    pub entry: QueueEntry,
+    /// When the entity will abandon the queue, if it has a patience.
+    pub abandon_at: Option<Duration>,
+}
+
+/// A node of the network that reacts to incoming and outgoing events.
+pub trait Block: Stats<Output = BlockStats> + StepStats<Output = BlockStepStats> + Send {
+    /// Name of the block, unique within its network.
     fn id(&self) -> BlockId;
-    fn next(&self, blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId>;
-    fn init(&mut self, _event_queue: &mut BinaryHeap<Event>) {}
+    /// Role of the block in the network topology.
+    fn kind(&self) -> BlockKind;
+    /// Every block this block can send events to.
+    fn targets(&self) -> Vec<BlockId>;
+    /// Blocks this block sends entities to once served, leaving out those
+    /// only reached by abandoning or balking.
+    fn route_targets(&self) -> Vec<BlockId> {
+        self.targets()
+    }
+    /// Reports configuration problems of the block.
+    fn validate(&self, _errors: &mut Vec<ValidationError>) {}
+    /// Chooses the block receiving `entity` as it leaves this one. An entity
+    /// routed nowhere leaves the network.
+    fn next(
+        &mut self,
+        entity: &mut Entity,
+        blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId>;
+    /// Reseeds the random streams of the block and its router.
+    fn seed(&mut self, _seed: u64) {}
+    /// Schedules the initial events before the simulation starts, creating
+    /// the entities they refer to.
+    fn init(
+        &mut self,
+        _entities: &mut Entities,
+        _event_queue: &mut EventQueue,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+    /// Discards the statistics collected so far, e.g. at the end of a warm-up
+    /// period.
+    fn reset_stats(&mut self, _simulation_duration: Duration) {}
+    /// Closes statistics still open when the simulation ends at
+    /// `simulation_duration`.
+    fn finish(&mut self, _simulation_duration: Duration) {}
+    /// Steady-state estimates of the block's metrics from the run so far,
+    /// observed until `simulation_duration`. Metrics without enough data are
+    /// left out.
+    fn batch_means(
+        &self,
+        _simulation_duration: Duration,
+        _confidence: f64,
+    ) -> Vec<(String, BatchMeans)> {
+        Vec::new()
+    }
+    /// Handles the arrival of an entity. Blocks that drop it, e.g. on
+    /// rejection, remove it from `entities`.
     fn process_in(
         &mut self,
-        _event_id: usize,
-        _event_queue: &mut BinaryHeap<Event>,
+        _entity_id: usize,
+        _entities: &mut Entities,
+        _event_queue: &mut EventQueue,
         _simulation_duration: Duration,
-    ) {
+    ) -> Result<(), SimulationError> {
+        Ok(())
     }
+    /// Handles the departure of an entity, after it has been routed.
     fn process_out(
         &mut self,
-        _event_id: usize,
-        _event_queue: &mut BinaryHeap<Event>,
+        _entity_id: usize,
+        _entities: &mut Entities,
+        _event_queue: &mut EventQueue,
+        _simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+    /// How the block handles entities whose next block is full, see
+    /// [`Blocking`].
+    fn blocking(&self) -> Blocking {
+        Blocking::Reject
+    }
+    /// Whether an entity arriving now would be served or queued rather than
+    /// rejected.
+    fn accepts(&self) -> bool {
+        true
+    }
+    /// Keeps `entity_id` on its device after its service ended, because its
+    /// next block is full. [`Block::process_out`] is only called once the
+    /// network lets it go with [`Block::unblock`].
+    fn block(
+        &mut self,
+        _entity_id: usize,
+        _simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+    /// Lets a blocked entity go, as its next block has room.
+    fn unblock(&mut self, _entity_id: usize, _simulation_duration: Duration) {}
+    /// Tells a block blocking before service whether every block it can send
+    /// to has room, letting it start held services when they do.
+    fn set_downstream_ready(
+        &mut self,
+        _ready: bool,
+        _event_queue: &mut EventQueue,
+        _simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+    /// Number of entities waiting in the queue of the block, `None` without a
+    /// queue. Only blocks with a queue can join a jockeying group.
+    fn queue_len(&self) -> Option<usize> {
+        None
+    }
+    /// Takes the entity the queue would serve last out of it to switch to a
+    /// shorter one, cancelling any event the block scheduled for it.
+    fn jockey_out(
+        &mut self,
+        _event_queue: &mut EventQueue,
+        _simulation_duration: Duration,
+    ) -> Option<Jockey> {
+        None
+    }
+    /// Receives an entity switching from a longer queue. Fails without
+    /// taking the entity if the block does not [`Block::accepts`] it.
+    fn jockey_in(
+        &mut self,
+        _jockey: Jockey,
+        _entities: &mut Entities,
+        _event_queue: &mut EventQueue,
+        _simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        Ok(())
+    }
+    /// Handles a timeout the block scheduled for an entity. The block moves
+    /// the entity on itself, if needed.
+    fn process_timeout(
+        &mut self,
+        _entity_id: usize,
+        _entities: &mut Entities,
+        _event_queue: &mut EventQueue,
         _simulation_duration: Duration,
-    ) {
+    ) -> Result<(), SimulationError> {
+        Ok(())
     }
 }
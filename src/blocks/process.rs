@@ -1,62 +1,271 @@
 use crate::{
-    blocks::{Block, BlockId},
-    devices::Devices,
-    events::{Event, EventType},
-    queue::Queue,
+    batch_means::BatchMeans,
+    blocks::{Block, BlockId, BlockKind, BlockStats, BlockStepStats, Blocking, Jockey},
+    devices::{Devices, DevicesStats, DevicesStepStats},
+    entity::{Entities, Entity},
+    error::{SimulationError, ValidationError},
+    events::{Event, EventHandle, EventQueue, EventType},
+    queue::{Queue, QueueEntry, QueueStats, QueueStepStats},
+    random::{stream_seed, unseeded_rng, SimulationRng},
     routers::Router,
-    stats::{Stats, StepStats},
-    weighted_average::weighted_total,
+    samples::{SampleStats, Samples},
+    stats::{prefixed, Metrics, Stats, StepStats},
 };
-use rand::{distr::Distribution, rng, Rng};
+use rand::{distr::Distribution, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BinaryHeap, HashMap},
-    fmt::Debug,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, VecDeque},
     time::Duration,
 };
 
-#[derive(Debug)]
-pub struct ProcessBlockStepStats<D, Q> {
+/// Instantaneous state of a [`ProcessBlock`]. Counters are totals so far, see
+/// [`ProcessBlockStats`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessBlockStepStats {
+    /// Entities whose service ended.
     pub processed: usize,
+    /// Arrivals turned away because every device was busy and the queue was
+    /// full or missing.
     pub rejections: usize,
-    pub devices: D,
+    /// Services interrupted by a higher priority arrival.
+    pub preemptions: usize,
+    /// Entities that left the queue when their patience ran out.
+    pub abandonments: usize,
+    /// Arrivals that chose not to join.
+    pub balks: usize,
+    /// Entities that switched to this block's queue from a longer one.
+    pub jockeys_in: usize,
+    /// Entities that switched from this block's queue to a shorter one.
+    pub jockeys_out: usize,
+    /// State of the devices.
+    pub devices: DevicesStepStats,
+    /// Fraction of rejected entities among those rejected or processed.
     pub rejection_probability: f32,
-    pub queue: Q,
+    /// State of the queue, if any.
+    pub queue: Option<QueueStepStats>,
 }
 
-#[derive(Debug)]
-pub struct ProcessBlockStats<D, Q> {
+/// Statistics of a [`ProcessBlock`] over the whole simulation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessBlockStats {
+    /// Entities whose service ended.
     pub processed: usize,
+    /// Arrivals turned away because every device was busy and the queue was
+    /// full or missing.
     pub rejections: usize,
-    pub devices: D,
+    /// Services interrupted by a higher priority arrival.
+    pub preemptions: usize,
+    /// Entities that left the queue when their patience ran out.
+    pub abandonments: usize,
+    /// Fraction of the entities leaving the block that abandoned the queue.
+    pub abandonment_rate: f32,
+    /// Arrivals that chose not to join, counted apart from `rejections`.
+    pub balks: usize,
+    /// Fraction of arrivals that balked.
+    pub balk_probability: f32,
+    /// Entities that switched to this block's queue from a longer one.
+    pub jockeys_in: usize,
+    /// Entities that switched from this block's queue to a shorter one.
+    pub jockeys_out: usize,
+    /// Statistics of the devices.
+    pub devices: DevicesStats,
+    /// Fraction of rejected entities among those rejected or processed.
     pub rejection_probability: f32,
-    pub queue: Q,
+    /// Statistics of the queue, if any.
+    pub queue: Option<QueueStats>,
+    /// Mean of `wait_time`.
     pub average_waited_time: f32,
+    /// Fraction of entities that had to queue before their service started.
+    pub wait_probability: f32,
+    /// Time entities waited before their service started, zero for entities
+    /// served on arrival. Rejected entities and entities still in the queue
+    /// are not included.
+    pub wait_time: SampleStats,
+    /// Time every service was scheduled to take when it started; after a
+    /// preemption, the time left.
+    pub service_time: SampleStats,
+    /// Statistics per priority class, only when entities of more than one
+    /// class arrived.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub classes: BTreeMap<i32, ClassStats>,
 }
 
+/// Statistics of the entities of one priority class at a process block.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassStats {
+    /// Entities that arrived, including those switching from another queue.
+    pub arrivals: usize,
+    /// Entities of the class whose service ended.
+    pub processed: usize,
+    /// Arrivals of the class that were turned away.
+    pub rejections: usize,
+    /// Entities of the class that abandoned the queue.
+    pub abandonments: usize,
+    /// Arrivals of the class that chose not to join.
+    pub balks: usize,
+    /// Time entities of the class waited before their service started.
+    pub wait_time: SampleStats,
+}
+
+/// Counters of one priority class, see [`ClassStats`].
+#[derive(Debug, Clone)]
+struct Class {
+    arrivals: usize,
+    processed: usize,
+    rejections: usize,
+    abandonments: usize,
+    balks: usize,
+    wait_times: Samples,
+}
+
+/// Samples the time an entity waits in the queue before abandoning it, in
+/// seconds.
+pub type Patience = Box<dyn Fn(&mut SimulationRng) -> f32 + Send>;
+
+/// Probability that an arrival joins the block, given the queue length and
+/// the number of busy devices it finds.
+pub type Balking = Box<dyn Fn(usize, usize) -> f64 + Send>;
+
+/// What happens to an entity whose service is interrupted by an arrival of a
+/// higher priority class finding every device busy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preemption {
+    /// Services are never interrupted.
+    #[default]
+    None,
+    /// The entity returns to the head of its class in the queue and later
+    /// resumes its service where it stopped.
+    Resume,
+    /// The entity returns to the head of its class in the queue and later
+    /// repeats its whole service, taking the same time again.
+    Restart,
+}
+
+/// An entity being served, with its scheduled `Out` event at `ends_at`.
+#[derive(Debug, Clone, Copy)]
+struct Service {
+    priority: i32,
+    started_at: Duration,
+    ends_at: Duration,
+    service_time: Duration,
+    out: EventHandle,
+}
+
+/// An entity holding a device without being served, until every block it
+/// can be sent to has room.
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    entity_id: usize,
+    priority: i32,
+    service_time: Option<Duration>,
+    waiting_since: Duration,
+}
+
+/// Service block with a pool of devices, service times drawn from `D` and an
+/// optional queue. Arrivals finding every device busy and the queue full (or
+/// missing) are rejected.
+///
+/// With [`Preemption`], an arrival finding every device busy instead takes
+/// over the device of the lowest priority entity in service, if that priority
+/// is lower than its own and the queue has room for the preempted entity.
+/// Wait and service times are recorded again when a preempted entity gets
+/// back to a device.
+///
+/// With a [`Patience`], every entity joining the queue draws how long it is
+/// willing to wait. If its service has not started by then, it abandons the
+/// queue and moves to the abandonment block, or leaves the network without
+/// one.
+///
+/// With [`Balking`], every arrival first decides whether to join at all.
+/// Arrivals that balk move to the balking block, or leave the network
+/// without one, and are not counted as rejections.
+///
+/// With a [`Blocking`] policy other than rejection, an entity whose next
+/// block is full when its service ends keeps its device blocked until the
+/// network lets it go. Time spent blocked is reported with the devices.
 pub struct ProcessBlock<D, R> {
+    /// Name of the block, unique within its network.
     pub id: BlockId,
+    /// Queue in front of the devices, if arrivals may wait.
     pub queue: Option<Queue>,
+    /// Devices serving entities.
     pub devices: Devices,
+    /// Entities whose service ended.
     pub processed: usize,
+    /// Arrivals turned away.
     pub rejections: usize,
+    /// Services interrupted by a higher priority arrival.
+    pub preemptions: usize,
+    /// Entities that abandoned the queue.
+    pub abandonments: usize,
+    /// Arrivals that chose not to join.
+    pub balks: usize,
+    /// Entities that switched to this block's queue.
+    pub jockeys_in: usize,
+    /// Entities that switched from this block's queue.
+    pub jockeys_out: usize,
+    /// Time every entity waited in the queue, in seconds, in the order they
+    /// started service. Zero for entities served on arrival.
+    pub wait_times: Samples,
+    /// Service time of every entity, in seconds, in the order they started
+    /// service.
+    pub service_times: Samples,
+    classes: BTreeMap<i32, Class>,
+    preemption: Preemption,
+    services: HashMap<usize, Service>,
+    patience: Option<Patience>,
+    abandon_to: Option<BlockId>,
+    /// Pending abandonment of every queued entity with a patience.
+    timeouts: HashMap<usize, EventHandle>,
+    balking: Option<Balking>,
+    balk_to: Option<BlockId>,
+    blocking: Blocking,
+    /// Whether every block the router can send to had room when the network
+    /// last checked, for [`Blocking::BeforeService`].
+    downstream_ready: bool,
+    held: VecDeque<Held>,
     router: R,
     distribution: D,
+    rng: SimulationRng,
+    patience_rng: SimulationRng,
+    balking_rng: SimulationRng,
 }
 
+/// Builder of a [`ProcessBlock`]. The service time distribution and the
+/// router must be set before it can be built.
 pub struct ProcessBlockBuilder<Distribution, Router> {
     id: BlockId,
     router: Router,
     distribution: Distribution,
     devices: Devices,
     queue: Option<Queue>,
+    wait_times: Samples,
+    service_times: Samples,
+    preemption: Preemption,
+    patience: Option<Patience>,
+    abandon_to: Option<BlockId>,
+    balking: Option<Balking>,
+    balk_to: Option<BlockId>,
+    blocking: Blocking,
 }
 
 impl<D> ProcessBlockBuilder<D, ()> {
+    /// Sets the router choosing the block every served entity moves to.
     pub fn router<R: Router>(self, router: R) -> ProcessBlockBuilder<D, R> {
         ProcessBlockBuilder {
             id: self.id,
             queue: self.queue,
             devices: self.devices,
+            wait_times: self.wait_times,
+            service_times: self.service_times,
+            preemption: self.preemption,
+            patience: self.patience,
+            abandon_to: self.abandon_to,
+            balking: self.balking,
+            balk_to: self.balk_to,
+            blocking: self.blocking,
             distribution: self.distribution,
             router,
         }
@@ -64,44 +273,150 @@ impl<D> ProcessBlockBuilder<D, ()> {
 }
 
 impl<R> ProcessBlockBuilder<(), R> {
+    /// Sets the distribution of service times, in seconds.
     pub fn distribution<D: Distribution<f32>>(self, distribution: D) -> ProcessBlockBuilder<D, R> {
         ProcessBlockBuilder {
             id: self.id,
             router: self.router,
             queue: self.queue,
             devices: self.devices,
+            wait_times: self.wait_times,
+            service_times: self.service_times,
+            preemption: self.preemption,
+            patience: self.patience,
+            abandon_to: self.abandon_to,
+            balking: self.balking,
+            balk_to: self.balk_to,
+            blocking: self.blocking,
             distribution,
         }
     }
 }
 
 impl<Distribution, Router> ProcessBlockBuilder<Distribution, Router> {
+    /// Lets arrivals finding every device busy wait in `queue` instead of
+    /// being rejected.
     pub fn queue(mut self, queue: impl Into<Queue>) -> Self {
         self.queue = Some(queue.into());
         self
     }
 
+    /// Sets the devices serving entities, a single one by default.
     pub fn devices(mut self, devices: impl Into<Devices>) -> Self {
         self.devices = devices.into();
         self
     }
+
+    /// Lets higher priority arrivals interrupt services, see [`Preemption`].
+    pub fn preemption(mut self, preemption: Preemption) -> Self {
+        self.preemption = preemption;
+        self
+    }
+
+    /// Lets queued entities abandon the queue after a patience drawn from
+    /// `patience`, see [`ProcessBlock`].
+    pub fn patience<P>(mut self, patience: P) -> Self
+    where
+        P: rand::distr::Distribution<f32> + Send + 'static,
+    {
+        self.patience = Some(Box::new(move |rng| patience.sample(rng)));
+        self
+    }
+
+    /// Sends entities abandoning the queue to `block` instead of removing them
+    /// from the network.
+    pub fn abandon_to(mut self, block: BlockId) -> Self {
+        self.abandon_to = Some(block);
+        self
+    }
+
+    /// Lets arrivals balk, joining with the probability `balking` gives for
+    /// the queue length and busy devices they find.
+    pub fn balking(mut self, balking: impl Fn(usize, usize) -> f64 + Send + 'static) -> Self {
+        self.balking = Some(Box::new(balking));
+        self
+    }
+
+    /// Lets arrivals balk, joining with the probability at the index of the
+    /// queue length they find. Lengths past the end of `probabilities` use
+    /// its last entry.
+    pub fn balking_table(self, probabilities: &[f64]) -> Self {
+        let probabilities = probabilities.to_vec();
+        self.balking(move |length, _| {
+            probabilities
+                .get(length)
+                .or(probabilities.last())
+                .copied()
+                .unwrap_or(1.0)
+        })
+    }
+
+    /// Sends arrivals that balk to `block` instead of removing them from the
+    /// network.
+    pub fn balk_to(mut self, block: BlockId) -> Self {
+        self.balk_to = Some(block);
+        self
+    }
+
+    /// Sets what happens to entities whose next block is full, see
+    /// [`Blocking`].
+    pub fn blocking(mut self, blocking: Blocking) -> Self {
+        self.blocking = blocking;
+        self
+    }
+
+    /// Sets how waits before service are summarized, by P² by default.
+    pub fn wait_times(mut self, wait_times: Samples) -> Self {
+        self.wait_times = wait_times;
+        self
+    }
+
+    /// Sets how service times are summarized, by P² by default.
+    pub fn service_times(mut self, service_times: Samples) -> Self {
+        self.service_times = service_times;
+        self
+    }
 }
 
 impl<D: Distribution<f32>, R: Router> ProcessBlockBuilder<D, R> {
+    /// Builds the block.
     pub fn build(self) -> ProcessBlock<D, R> {
         ProcessBlock {
             id: self.id,
             queue: self.queue,
             processed: 0,
             rejections: 0,
+            preemptions: 0,
+            abandonments: 0,
+            balks: 0,
+            jockeys_in: 0,
+            jockeys_out: 0,
+            wait_times: self.wait_times,
+            service_times: self.service_times,
+            preemption: self.preemption,
+            classes: BTreeMap::new(),
+            services: HashMap::new(),
+            patience: self.patience,
+            abandon_to: self.abandon_to,
+            timeouts: HashMap::new(),
+            balking: self.balking,
+            balk_to: self.balk_to,
+            blocking: self.blocking,
+            downstream_ready: true,
+            held: VecDeque::new(),
             devices: self.devices,
             router: self.router,
             distribution: self.distribution,
+            rng: unseeded_rng(),
+            patience_rng: unseeded_rng(),
+            balking_rng: unseeded_rng(),
         }
     }
 }
 
 impl ProcessBlock<(), ()> {
+    /// Starts building a process block named `id`, with a single device and
+    /// no queue.
     pub fn builder(id: BlockId) -> ProcessBlockBuilder<(), ()> {
         ProcessBlockBuilder {
             id,
@@ -109,121 +424,980 @@ impl ProcessBlock<(), ()> {
             distribution: (),
             devices: Devices::default(),
             queue: None,
+            wait_times: Samples::new(),
+            service_times: Samples::new(),
+            preemption: Preemption::None,
+            patience: None,
+            abandon_to: None,
+            balking: None,
+            balk_to: None,
+            blocking: Blocking::Reject,
         }
     }
 }
 
 impl<D: Distribution<f32>, R: Router> ProcessBlock<D, R> {
-    fn delay(&self) -> Duration {
-        Duration::from_secs_f32(rng().sample(&self.distribution).max(0.0))
+    fn delay(&mut self) -> Result<Duration, SimulationError> {
+        let value = self.rng.sample(&self.distribution);
+        Duration::try_from_secs_f32(value.max(0.0)).map_err(|_| SimulationError::InvalidDelay {
+            block: self.id,
+            value,
+        })
+    }
+
+    /// Loads `entity_id`, waiting since `waiting_since`, on an idle device
+    /// and schedules the end of its service, sampling the service time unless
+    /// given. Blocking before service, the entity is held on the device
+    /// instead while the next blocks are full.
+    fn serve(
+        &mut self,
+        entity_id: usize,
+        priority: i32,
+        service_time: Option<Duration>,
+        waiting_since: Duration,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        self.devices.load(entity_id, simulation_duration)?;
+        if self.blocking == Blocking::BeforeService && !self.downstream_ready {
+            self.devices.block(simulation_duration);
+            self.held.push_back(Held {
+                entity_id,
+                priority,
+                service_time,
+                waiting_since,
+            });
+            return Ok(());
+        }
+        self.start(
+            entity_id,
+            priority,
+            service_time,
+            waiting_since,
+            event_queue,
+            simulation_duration,
+        )
+    }
+
+    /// Starts the service of `entity_id`, already loaded on a device.
+    fn start(
+        &mut self,
+        entity_id: usize,
+        priority: i32,
+        service_time: Option<Duration>,
+        waiting_since: Duration,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        let waited = (simulation_duration - waiting_since).as_secs_f64();
+        let service_time = match service_time {
+            Some(service_time) => service_time,
+            None => self.delay()?,
+        };
+        self.wait_times.push(waited);
+        self.service_times.push(service_time.as_secs_f64());
+        self.class(priority).wait_times.push(waited);
+        let ends_at = simulation_duration + service_time;
+        let out = event_queue.push(Event(ends_at, self.id, EventType::Out, entity_id));
+        self.services.insert(
+            entity_id,
+            Service {
+                priority,
+                started_at: simulation_duration,
+                ends_at,
+                service_time,
+                out,
+            },
+        );
+        Ok(())
+    }
+
+    /// Entity in service that an arrival of class `priority` would preempt:
+    /// the lowest class below `priority`, the latest started within a class.
+    fn preemptible(&self, priority: i32) -> Option<usize> {
+        if self.preemption == Preemption::None
+            || self.queue.as_ref().is_none_or(|queue| queue.is_full())
+        {
+            return None;
+        }
+        self.services
+            .iter()
+            .filter(|(_, service)| service.priority < priority)
+            .min_by_key(|&(&entity_id, service)| {
+                (
+                    service.priority,
+                    Reverse(service.started_at),
+                    Reverse(entity_id),
+                )
+            })
+            .map(|(&entity_id, _)| entity_id)
+    }
+
+    /// Interrupts the service of `entity_id`, cancelling its `Out` event, and
+    /// returns it to the queue with the service time it still needs.
+    fn preempt(
+        &mut self,
+        entity_id: usize,
+        entities: &Entities,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        let service = self
+            .services
+            .remove(&entity_id)
+            .ok_or(SimulationError::NotInService(entity_id))?;
+        event_queue.cancel(service.out);
+        self.devices.unload(entity_id, simulation_duration)?;
+        let remaining = match self.preemption {
+            Preemption::Restart => service.service_time,
+            _ => service.ends_at - simulation_duration,
+        };
+        let entity = entities
+            .get(entity_id)
+            .ok_or(SimulationError::UnknownEntity(entity_id))?;
+        if let Some(queue) = &mut self.queue {
+            queue.requeue(entity, Some(remaining), simulation_duration);
+        }
+        self.preemptions += 1;
+        Ok(())
+    }
+
+    /// Counters of the priority class `priority`, summarizing waits the same
+    /// way as `wait_times`.
+    fn class(&mut self, priority: i32) -> &mut Class {
+        self.classes.entry(priority).or_insert_with(|| {
+            let mut wait_times = self.wait_times.clone();
+            wait_times.clear();
+            Class {
+                arrivals: 0,
+                processed: 0,
+                rejections: 0,
+                abandonments: 0,
+                balks: 0,
+                wait_times,
+            }
+        })
     }
 }
 
 impl<D: Distribution<f32>, R: Router> Stats for ProcessBlock<D, R> {
-    fn stats(&self) -> Box<dyn Debug> {
-        Box::new(ProcessBlockStats {
+    type Output = BlockStats;
+
+    fn stats(&self) -> BlockStats {
+        let wait_time = self.wait_times.stats();
+        BlockStats::Process(Box::new(ProcessBlockStats {
             processed: self.processed,
             rejections: self.rejections,
+            preemptions: self.preemptions,
+            abandonments: self.abandonments,
+            abandonment_rate: self.abandonments as f32
+                / (self.processed + self.rejections + self.abandonments + self.balks) as f32,
+            balks: self.balks,
+            balk_probability: self.balks as f32
+                / self
+                    .classes
+                    .values()
+                    .map(|class| class.arrivals)
+                    .sum::<usize>() as f32,
+            jockeys_in: self.jockeys_in,
+            jockeys_out: self.jockeys_out,
             devices: self.devices.stats(),
             rejection_probability: self.rejections as f32
                 / (self.rejections + self.processed) as f32,
-            queue: self
-                .queue
-                .as_ref()
-                .map(|q| q.stats())
-                .unwrap_or(Box::new(None::<()>)),
-            average_waited_time: self
-                .queue
-                .as_ref()
-                .map(|q| weighted_total(&q.lengths) / self.processed as f32)
-                .unwrap_or(0.0),
-        })
+            queue: self.queue.as_ref().map(|q| q.stats()),
+            average_waited_time: wait_time.mean as f32,
+            wait_probability: self.queue.as_ref().map_or(0, |q| q.waits.len()) as f32
+                / self.wait_times.len() as f32,
+            wait_time,
+            service_time: self.service_times.stats(),
+            classes: if self.classes.len() > 1 {
+                self.classes
+                    .iter()
+                    .map(|(&priority, class)| {
+                        (
+                            priority,
+                            ClassStats {
+                                arrivals: class.arrivals,
+                                processed: class.processed,
+                                rejections: class.rejections,
+                                abandonments: class.abandonments,
+                                balks: class.balks,
+                                wait_time: class.wait_times.stats(),
+                            },
+                        )
+                    })
+                    .collect()
+            } else {
+                BTreeMap::new()
+            },
+        }))
     }
 }
 
 impl<D: Distribution<f32>, R: Router> StepStats for ProcessBlock<D, R> {
-    fn step_stats(&self) -> Box<dyn Debug> {
-        Box::new(ProcessBlockStepStats {
+    type Output = BlockStepStats;
+
+    fn step_stats(&self) -> BlockStepStats {
+        BlockStepStats::Process(ProcessBlockStepStats {
             processed: self.processed,
             rejections: self.rejections,
+            preemptions: self.preemptions,
+            abandonments: self.abandonments,
+            balks: self.balks,
+            jockeys_in: self.jockeys_in,
+            jockeys_out: self.jockeys_out,
             devices: self.devices.step_stats(),
             rejection_probability: self.rejections as f32
                 / (self.rejections + self.processed) as f32,
-            queue: self
-                .queue
-                .as_ref()
-                .map(|q| q.step_stats())
-                .unwrap_or(Box::new(None::<()>)),
+            queue: self.queue.as_ref().map(|q| q.step_stats()),
         })
     }
 }
 
-impl<D: Distribution<f32>, R: Router> Block for ProcessBlock<D, R> {
+impl Metrics for ProcessBlockStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![
+            ("processed".into(), self.processed as f64),
+            ("rejections".into(), self.rejections as f64),
+            ("preemptions".into(), self.preemptions as f64),
+            ("abandonments".into(), self.abandonments as f64),
+            ("abandonment_rate".into(), self.abandonment_rate as f64),
+            ("balks".into(), self.balks as f64),
+            ("balk_probability".into(), self.balk_probability as f64),
+            ("jockeys_in".into(), self.jockeys_in as f64),
+            ("jockeys_out".into(), self.jockeys_out as f64),
+            (
+                "rejection_probability".into(),
+                self.rejection_probability as f64,
+            ),
+            (
+                "average_waited_time".into(),
+                self.average_waited_time as f64,
+            ),
+            ("wait_probability".into(), self.wait_probability as f64),
+        ];
+        metrics.extend(prefixed("wait_time", &self.wait_time));
+        metrics.extend(prefixed("service_time", &self.service_time));
+        metrics.extend(prefixed("devices", &self.devices));
+        metrics.extend(prefixed("queue", &self.queue));
+        for (priority, class) in &self.classes {
+            metrics.extend(prefixed(&format!("class.{priority}"), class));
+        }
+        metrics
+    }
+}
+
+impl Metrics for ClassStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![
+            ("arrivals".into(), self.arrivals as f64),
+            ("processed".into(), self.processed as f64),
+            ("rejections".into(), self.rejections as f64),
+            ("abandonments".into(), self.abandonments as f64),
+            ("balks".into(), self.balks as f64),
+        ];
+        metrics.extend(prefixed("wait_time", &self.wait_time));
+        metrics
+    }
+}
+
+impl Metrics for ProcessBlockStepStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![
+            ("processed".into(), self.processed as f64),
+            ("rejections".into(), self.rejections as f64),
+            ("preemptions".into(), self.preemptions as f64),
+            ("abandonments".into(), self.abandonments as f64),
+            ("balks".into(), self.balks as f64),
+            ("jockeys_in".into(), self.jockeys_in as f64),
+            ("jockeys_out".into(), self.jockeys_out as f64),
+            (
+                "rejection_probability".into(),
+                self.rejection_probability as f64,
+            ),
+        ];
+        metrics.extend(prefixed("devices", &self.devices));
+        metrics.extend(prefixed("queue", &self.queue));
+        metrics
+    }
+}
+
+impl<D: Distribution<f32> + Send, R: Router> Block for ProcessBlock<D, R> {
     fn id(&self) -> BlockId {
         self.id
     }
 
-    fn next(&self, blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId> {
-        self.router.next(blocks)
+    fn kind(&self) -> BlockKind {
+        BlockKind::Process
+    }
+
+    fn targets(&self) -> Vec<BlockId> {
+        let mut targets = self.router.targets();
+        targets.extend(self.abandon_to);
+        targets.extend(self.balk_to);
+        targets
+    }
+
+    fn route_targets(&self) -> Vec<BlockId> {
+        self.router.targets()
     }
 
-    fn init(&mut self, event_queue: &mut BinaryHeap<Event>) {
-        for event_id in &self.devices.workers {
-            if let &Some(event_id) = event_id {
-                event_queue.push(Event(self.delay(), self.id, EventType::Out, event_id));
+    fn validate(&self, errors: &mut Vec<ValidationError>) {
+        self.router.validate(self.id, errors);
+    }
+
+    fn next(
+        &mut self,
+        entity: &mut Entity,
+        blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
+        self.router.next(entity, blocks)
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = SimulationRng::seed_from_u64(seed);
+        self.patience_rng = SimulationRng::seed_from_u64(stream_seed(seed, "patience"));
+        self.balking_rng = SimulationRng::seed_from_u64(stream_seed(seed, "balking"));
+        self.router.seed(stream_seed(seed, "router"));
+        if let Some(queue) = &mut self.queue {
+            queue.seed(stream_seed(seed, "queue"));
+        }
+    }
+
+    fn init(
+        &mut self,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+    ) -> Result<(), SimulationError> {
+        self.devices.reset_stats(Duration::ZERO);
+        let entity_ids = self
+            .devices
+            .workers
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        for entity_id in entity_ids {
+            if !entities.contains(entity_id) {
+                entities.insert(Entity::new(entity_id, Duration::ZERO));
             }
+            let delay = self.delay()?;
+            let out = event_queue.push(Event(delay, self.id, EventType::Out, entity_id));
+            self.services.insert(
+                entity_id,
+                Service {
+                    priority: entities.get(entity_id).map_or(0, |entity| entity.priority),
+                    started_at: Duration::ZERO,
+                    ends_at: delay,
+                    service_time: delay,
+                    out,
+                },
+            );
         }
+        Ok(())
     }
 
-    fn process_in(
+    fn batch_means(
+        &self,
+        simulation_duration: Duration,
+        confidence: f64,
+    ) -> Vec<(String, BatchMeans)> {
+        let mut estimates = vec![
+            (
+                "wait_time".to_string(),
+                BatchMeans::from_batched_samples(&self.wait_times, confidence),
+            ),
+            (
+                "devices.workload".to_string(),
+                BatchMeans::from_time_weighted(
+                    &self.devices.workloads,
+                    simulation_duration,
+                    confidence,
+                ),
+            ),
+        ];
+        if let Some(queue) = &self.queue {
+            estimates.push((
+                "queue.length".to_string(),
+                BatchMeans::from_time_weighted(&queue.lengths, simulation_duration, confidence),
+            ));
+        }
+        estimates
+            .into_iter()
+            .filter_map(|(metric, estimate)| Some((metric, estimate?)))
+            .collect()
+    }
+
+    fn finish(&mut self, simulation_duration: Duration) {
+        self.devices.finish(simulation_duration);
+        if let Some(queue) = &mut self.queue {
+            queue.finish(simulation_duration);
+        }
+    }
+
+    fn reset_stats(&mut self, simulation_duration: Duration) {
+        self.processed = 0;
+        self.rejections = 0;
+        self.preemptions = 0;
+        self.abandonments = 0;
+        self.balks = 0;
+        self.jockeys_in = 0;
+        self.jockeys_out = 0;
+        self.wait_times.clear();
+        self.service_times.clear();
+        self.classes.clear();
+        self.devices.reset_stats(simulation_duration);
+        if let Some(queue) = &mut self.queue {
+            queue.reset_stats(simulation_duration);
+        }
+    }
+
+    fn blocking(&self) -> Blocking {
+        self.blocking
+    }
+
+    fn accepts(&self) -> bool {
+        self.devices.idle() != 0 || self.queue.as_ref().is_some_and(|queue| !queue.is_full())
+    }
+
+    fn block(
+        &mut self,
+        entity_id: usize,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        self.services
+            .remove(&entity_id)
+            .ok_or(SimulationError::NotInService(entity_id))?;
+        self.devices.block(simulation_duration);
+        Ok(())
+    }
+
+    fn unblock(&mut self, _entity_id: usize, simulation_duration: Duration) {
+        self.devices.unblock(simulation_duration);
+    }
+
+    fn set_downstream_ready(
         &mut self,
-        event_id: usize,
-        event_queue: &mut BinaryHeap<Event>,
+        ready: bool,
+        event_queue: &mut EventQueue,
         simulation_duration: Duration,
-    ) {
+    ) -> Result<(), SimulationError> {
+        self.downstream_ready = ready;
+        if !ready {
+            return Ok(());
+        }
+        while let Some(held) = self.held.pop_front() {
+            self.devices.unblock(simulation_duration);
+            self.start(
+                held.entity_id,
+                held.priority,
+                held.service_time,
+                held.waiting_since,
+                event_queue,
+                simulation_duration,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn queue_len(&self) -> Option<usize> {
+        self.queue.as_ref().map(Queue::len)
+    }
+
+    fn jockey_out(
+        &mut self,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Option<Jockey> {
+        let entry = self.queue.as_mut()?.remove_last(simulation_duration)?;
+        let abandon_at = self
+            .timeouts
+            .remove(&entry.entity_id)
+            .and_then(|timeout| event_queue.cancel(timeout))
+            .map(|Event(time, ..)| time);
+        self.jockeys_out += 1;
+        Some(Jockey { entry, abandon_at })
+    }
+
+    fn jockey_in(
+        &mut self,
+        jockey: Jockey,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        if !self.accepts() {
+            return Err(SimulationError::QueueFull(self.id));
+        }
+        let Jockey { entry, abandon_at } = jockey;
+        self.jockeys_in += 1;
+        self.class(entry.priority).arrivals += 1;
+        let kept = entry.service_time.filter(|_| entry.preempted);
         if self.devices.idle() != 0 {
-            self.devices.load(event_id, simulation_duration);
-            event_queue.push(Event(
-                simulation_duration + self.delay(),
-                self.id,
-                EventType::Out,
-                event_id,
-            ));
+            return self.serve(
+                entry.entity_id,
+                entry.priority,
+                kept,
+                entry.enqueued_at,
+                event_queue,
+                simulation_duration,
+            );
+        }
+        let entity = entities
+            .get(entry.entity_id)
+            .ok_or(SimulationError::UnknownEntity(entry.entity_id))?;
+        let service_time = if kept.is_some() {
+            kept
+        } else if self
+            .queue
+            .as_ref()
+            .is_some_and(Queue::presamples_service_time)
+        {
+            Some(self.delay()?)
         } else {
-            let Some(queue) = &mut self.queue else {
-                self.rejections += 1;
-                return;
+            None
+        };
+        if let Some(queue) = &mut self.queue {
+            let entry = QueueEntry {
+                service_time,
+                ..entry
             };
-            if queue.len() < queue.capacity.unwrap_or(usize::MAX) {
-                queue.enqueue(event_id, simulation_duration);
-            } else {
-                self.rejections += 1;
+            queue.transfer(entity, entry, simulation_duration);
+        }
+        if let Some(abandon_at) = abandon_at {
+            let timeout = event_queue.push(Event(
+                abandon_at,
+                self.id,
+                EventType::Timeout,
+                entry.entity_id,
+            ));
+            self.timeouts.insert(entry.entity_id, timeout);
+        }
+        Ok(())
+    }
+
+    fn process_in(
+        &mut self,
+        entity_id: usize,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        let entity = entities
+            .get(entity_id)
+            .ok_or(SimulationError::UnknownEntity(entity_id))?;
+        let priority = entity.priority;
+        self.class(priority).arrivals += 1;
+        if let Some(balking) = &self.balking {
+            let length = self.queue.as_ref().map_or(0, Queue::len);
+            if self.balking_rng.random::<f64>() >= balking(length, self.devices.busy) {
+                self.balks += 1;
+                self.class(priority).balks += 1;
+                divert(
+                    entity_id,
+                    self.balk_to,
+                    entities,
+                    event_queue,
+                    simulation_duration,
+                );
+                return Ok(());
             }
         }
+        if self.devices.idle() != 0 {
+            return self.serve(
+                entity_id,
+                priority,
+                None,
+                simulation_duration,
+                event_queue,
+                simulation_duration,
+            );
+        }
+        if let Some(preempted) = self.preemptible(priority) {
+            self.preempt(preempted, entities, event_queue, simulation_duration)?;
+            return self.serve(
+                entity_id,
+                priority,
+                None,
+                simulation_duration,
+                event_queue,
+                simulation_duration,
+            );
+        }
+        let Some(presample) = self
+            .queue
+            .as_ref()
+            .filter(|queue| !queue.is_full())
+            .map(Queue::presamples_service_time)
+        else {
+            self.rejections += 1;
+            self.class(priority).rejections += 1;
+            entities.remove(entity_id);
+            return Ok(());
+        };
+        let service_time = if presample { Some(self.delay()?) } else { None };
+        if let Some(queue) = &mut self.queue {
+            queue.enqueue(entity, service_time, simulation_duration);
+        }
+        if let Some(patience) = &self.patience {
+            let value = patience(&mut self.patience_rng);
+            let patience = Duration::try_from_secs_f32(value.max(0.0)).map_err(|_| {
+                SimulationError::InvalidDelay {
+                    block: self.id,
+                    value,
+                }
+            })?;
+            let timeout = event_queue.push(Event(
+                simulation_duration + patience,
+                self.id,
+                EventType::Timeout,
+                entity_id,
+            ));
+            self.timeouts.insert(entity_id, timeout);
+        }
+        Ok(())
     }
 
     fn process_out(
         &mut self,
-        event_id: usize,
-        event_queue: &mut BinaryHeap<Event>,
+        entity_id: usize,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
         simulation_duration: Duration,
-    ) {
+    ) -> Result<(), SimulationError> {
+        let priority = entities
+            .get(entity_id)
+            .ok_or(SimulationError::UnknownEntity(entity_id))?
+            .priority;
         self.processed += 1;
-        let delay = self.delay();
-        let Some(queue) = &mut self.queue else {
-            self.devices.unload(event_id, simulation_duration);
-            return;
+        self.class(priority).processed += 1;
+        let delay = self.delay()?;
+        self.services.remove(&entity_id);
+        self.devices.unload(entity_id, simulation_duration)?;
+        let Some(queue) = self.queue.as_mut().filter(|queue| !queue.is_empty()) else {
+            return Ok(());
         };
-        self.devices.unload(event_id, simulation_duration);
-        if queue.len() != 0 {
-            let next_event_id = queue.dequeue(simulation_duration);
-            self.devices.load(next_event_id, simulation_duration);
-            event_queue.push(Event(
-                simulation_duration + delay,
-                self.id,
+        if self.blocking == Blocking::BeforeService {
+            // The leaving entity may fill a next block, so the next service
+            // waits for the network to check again once this event is done.
+            self.downstream_ready = false;
+        }
+        let entry = queue.dequeue(simulation_duration)?;
+        if let Some(timeout) = self.timeouts.remove(&entry.entity_id) {
+            event_queue.cancel(timeout);
+        }
+        self.serve(
+            entry.entity_id,
+            entry.priority,
+            Some(entry.service_time.unwrap_or(delay)),
+            entry.enqueued_at,
+            event_queue,
+            simulation_duration,
+        )
+    }
+
+    fn process_timeout(
+        &mut self,
+        entity_id: usize,
+        entities: &mut Entities,
+        event_queue: &mut EventQueue,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
+        if self.timeouts.remove(&entity_id).is_none() {
+            return Ok(());
+        }
+        let Some(entry) = self
+            .queue
+            .as_mut()
+            .and_then(|queue| queue.remove(entity_id, simulation_duration))
+        else {
+            return Ok(());
+        };
+        self.abandonments += 1;
+        self.class(entry.priority).abandonments += 1;
+        divert(
+            entity_id,
+            self.abandon_to,
+            entities,
+            event_queue,
+            simulation_duration,
+        );
+        Ok(())
+    }
+}
+
+/// Sends an entity leaving without service to `block`, or out of the network.
+fn divert(
+    entity_id: usize,
+    block: Option<BlockId>,
+    entities: &mut Entities,
+    event_queue: &mut EventQueue,
+    simulation_duration: Duration,
+) {
+    match block {
+        Some(block) => {
+            event_queue.push(Event(simulation_duration, block, EventType::In, entity_id));
+        }
+        None => {
+            entities.remove(entity_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blocks::{CreateBlock, DisposeBlock},
+        distributions::Deterministic,
+        network::QueueNetwork,
+        routers::DirectRouter,
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn block(service_time: f32, queue: Queue) -> ProcessBlock<Deterministic, DirectRouter> {
+        ProcessBlock::builder("block")
+            .distribution(Deterministic::new(service_time))
+            .queue(queue)
+            .router(DirectRouter::new("dispose"))
+            .build()
+    }
+
+    fn jockey(entity_id: usize, service_time: u64, preempted: bool) -> Jockey {
+        Jockey {
+            entry: QueueEntry {
+                entity_id,
+                priority: 0,
+                enqueued_at: Duration::from_secs(1),
+                service_time: Some(Duration::from_secs(service_time)),
+                rank: 0.0,
+                preempted,
+            },
+            abandon_at: None,
+        }
+    }
+
+    fn stats(block: &(impl Block + ?Sized)) -> ProcessBlockStats {
+        match block.stats() {
+            BlockStats::Process(stats) => *stats,
+            _ => panic!("not a process block"),
+        }
+    }
+
+    #[test]
+    fn jockey_in_refuses_a_full_queue() {
+        let mut block = block(10.0, Queue::from_capacity(1));
+        let mut entities = Entities::new();
+        let mut events = EventQueue::new();
+        for _ in 0..3 {
+            let id = entities.create(Duration::ZERO).id;
+            block
+                .process_in(id, &mut entities, &mut events, Duration::ZERO)
+                .unwrap();
+        }
+        assert_eq!(block.rejections, 1);
+        let id = entities.create(Duration::ZERO).id;
+        let result = block.jockey_in(
+            jockey(id, 10, false),
+            &mut entities,
+            &mut events,
+            Duration::ZERO,
+        );
+        assert!(matches!(result, Err(SimulationError::QueueFull("block"))));
+        assert_eq!(block.jockeys_in, 0);
+        assert_eq!(block.queue_len(), Some(1));
+    }
+
+    #[test]
+    fn jockey_in_keeps_the_remaining_time_of_a_preempted_entity() {
+        let mut entities = Entities::new();
+        let mut events = EventQueue::new();
+        let preempted = entities.create(Duration::ZERO).id;
+        let fresh = entities.create(Duration::ZERO).id;
+        let now = Duration::from_secs(5);
+
+        let mut idle = block(100.0, Queue::default());
+        idle.jockey_in(jockey(preempted, 8, true), &mut entities, &mut events, now)
+            .unwrap();
+        assert_eq!(
+            events.pop(),
+            Some(Event(
+                Duration::from_secs(13),
+                "block",
                 EventType::Out,
-                next_event_id,
-            ));
+                preempted
+            ))
+        );
+
+        let mut idle = block(100.0, Queue::default());
+        idle.jockey_in(jockey(fresh, 8, false), &mut entities, &mut events, now)
+            .unwrap();
+        assert_eq!(
+            events.pop(),
+            Some(Event(
+                Duration::from_secs(105),
+                "block",
+                EventType::Out,
+                fresh
+            ))
+        );
+    }
+
+    #[test]
+    fn jockeyed_in_entities_count_as_arrivals() {
+        let mut block = block(1.0, Queue::default());
+        let mut entities = Entities::new();
+        let mut events = EventQueue::new();
+        let id = entities.create(Duration::ZERO).id;
+        let mut other = entities.create(Duration::ZERO).clone();
+        other.priority = 1;
+        entities.insert(other.clone());
+        block
+            .process_in(other.id, &mut entities, &mut events, Duration::ZERO)
+            .unwrap();
+        block
+            .jockey_in(
+                jockey(id, 1, false),
+                &mut entities,
+                &mut events,
+                Duration::ZERO,
+            )
+            .unwrap();
+        while let Some(Event(time, _, _, entity_id)) = events.pop() {
+            block
+                .process_out(entity_id, &mut entities, &mut events, time)
+                .unwrap();
+        }
+        let stats = stats(&block);
+        assert_eq!(stats.jockeys_in, 1);
+        for class in stats.classes.values() {
+            assert_eq!(class.arrivals, 1);
+            assert_eq!(class.processed, 1);
         }
     }
+
+    /// Times and entities of every service completed when a low priority
+    /// entity arriving at 0 is preempted by a high priority one arriving at 2,
+    /// both needing 10 seconds.
+    fn preempted_completions(preemption: Preemption) -> (Vec<(u64, usize)>, usize) {
+        let source = |id, first_at, priority| {
+            CreateBlock::builder(id)
+                .distribution(Deterministic::new(1000.0))
+                .router(DirectRouter::new("process"))
+                .first_at(Duration::from_secs(first_at))
+                .priority(priority)
+                .build()
+        };
+        let completions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&completions);
+        let mut network = QueueNetwork::new()
+            .add_block(source("low", 0, 0))
+            .add_block(source("high", 2, 1))
+            .add_block(
+                ProcessBlock::builder("process")
+                    .distribution(Deterministic::new(10.0))
+                    .queue(Queue::default().priority())
+                    .preemption(preemption)
+                    .router(DirectRouter::new("dispose"))
+                    .build(),
+            )
+            .add_block(DisposeBlock::new("dispose"))
+            .on_simulation_step(move |network, Event(time, block, event_type, id)| {
+                if block == "process" && event_type == EventType::Out {
+                    let priority = network.entities.get(id).unwrap().priority;
+                    recorded
+                        .lock()
+                        .unwrap()
+                        .push((time.as_secs(), priority as usize));
+                }
+            });
+        network.simulate(Duration::from_secs(100)).unwrap();
+        let preemptions = stats(&*network.blocks["process"]).preemptions;
+        let completions = completions.lock().unwrap().clone();
+        (completions, preemptions)
+    }
+
+    #[test]
+    fn preempted_service_resumes() {
+        assert_eq!(
+            preempted_completions(Preemption::Resume),
+            (vec![(12, 1), (20, 0)], 1)
+        );
+    }
+
+    #[test]
+    fn preempted_service_restarts() {
+        assert_eq!(
+            preempted_completions(Preemption::Restart),
+            (vec![(12, 1), (22, 0)], 1)
+        );
+    }
+
+    /// Network sending arrivals every second to `process`, with served
+    /// entities and those leaving to `elsewhere` disposed apart.
+    fn impatient(
+        process: ProcessBlock<Deterministic, DirectRouter>,
+        elsewhere: BlockId,
+    ) -> QueueNetwork {
+        QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(DirectRouter::new("block"))
+                    .build(),
+            )
+            .add_block(process)
+            .add_block(DisposeBlock::new("dispose"))
+            .add_block(DisposeBlock::new(elsewhere))
+    }
+
+    fn disposed(network: &QueueNetwork, block_id: BlockId) -> usize {
+        match network.blocks[block_id].stats() {
+            BlockStats::Dispose(stats) => stats.disposed_events,
+            _ => panic!("{block_id} is not a dispose block"),
+        }
+    }
+
+    #[test]
+    fn queued_entities_renege_after_their_patience() {
+        let process = ProcessBlock::builder("block")
+            .distribution(Deterministic::new(10.0))
+            .queue(Queue::default())
+            .patience(Deterministic::new(2.5))
+            .abandon_to("abandoned")
+            .router(DirectRouter::new("dispose"))
+            .build();
+        let mut network = impatient(process, "abandoned");
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let stats = stats(&*network.blocks["block"]);
+        // The entity arriving at 8 is served from 10; those arriving at 1 to
+        // 7 and 9 to 17 abandon 2.5 seconds after arriving.
+        assert_eq!(stats.processed, 1);
+        assert_eq!(stats.abandonments, 16);
+        assert_eq!(stats.abandonment_rate, 16.0 / 17.0);
+        assert_eq!(stats.rejections, 0);
+        assert_eq!(disposed(&network, "abandoned"), 16);
+    }
+
+    #[test]
+    fn arrivals_balk_apart_from_rejections() {
+        let process = ProcessBlock::builder("block")
+            .distribution(Deterministic::new(2.5))
+            .queue(Queue::from_capacity(1))
+            .balking_table(&[1.0, 0.0])
+            .balk_to("balked")
+            .router(DirectRouter::new("dispose"))
+            .build();
+        let mut network = impatient(process, "balked");
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let stats = stats(&*network.blocks["block"]);
+        // Arrivals finding the queue empty join: those at 0, 1, 3, 5, 8, 10,
+        // 13, 15 and 18. The other 11 of 20 balk.
+        assert_eq!(stats.processed, 7);
+        assert_eq!(stats.balks, 11);
+        assert_eq!(stats.balk_probability, 11.0 / 20.0);
+        assert_eq!(stats.rejections, 0);
+        assert_eq!(stats.rejection_probability, 0.0);
+        assert_eq!(disposed(&network, "balked"), 11);
+    }
 }
@@ -1,24 +1,58 @@
+//! Servers of a process block and their time-weighted workload.
+
 use crate::{
-    stats::{Stats, StepStats},
-    weighted_average::weighted_average,
+    error::SimulationError,
+    stats::{Metrics, Stats, StepStats},
+    time_weighted::TimeWeighted,
 };
-use std::{fmt::Debug, time::Duration};
+use serde::Serialize;
+use std::time::Duration;
 
+/// A pool of identical servers, each holding at most one entity id.
+///
+/// Every change in workload updates the time-weighted statistics in
+/// `workloads`, starting with the workload at time zero. Busy devices may be
+/// blocked, holding an entity without serving it; the fraction of blocked
+/// devices is tracked the same way in `blocked_loads`.
 pub struct Devices {
+    /// Devices holding an entity, blocked or not.
     pub busy: usize,
+    /// Busy devices holding an entity they are done serving or cannot start
+    /// serving.
+    pub blocked: usize,
+    /// Id of the entity held by every device, if any.
     pub workers: Vec<Option<usize>>,
-    pub workloads: Vec<(Duration, f32)>,
+    /// Fraction of busy devices over time.
+    pub workloads: TimeWeighted,
+    /// Fraction of blocked devices over time.
+    pub blocked_loads: TimeWeighted,
 }
 
-#[derive(Debug)]
+/// Statistics of [`Devices`] over the whole simulation. Workloads are
+/// fractions of busy devices.
+#[derive(Debug, Clone, Serialize)]
 pub struct DevicesStats {
-    final_workload: f32,
-    average_workload: f32,
+    /// Workload at the end of the simulation.
+    pub final_workload: f32,
+    /// Time-weighted average workload, the utilization of the devices.
+    pub average_workload: f32,
+    /// Time-weighted variance of the workload.
+    pub workload_variance: f32,
+    /// Lowest workload reached.
+    pub min_workload: f32,
+    /// Highest workload reached.
+    pub max_workload: f32,
+    /// Time-weighted fraction of devices blocked.
+    pub average_blocked: f32,
+    /// Total time devices spent blocked, in seconds.
+    pub blocked_time: f32,
 }
 
-#[derive(Debug)]
+/// Instantaneous state of [`Devices`].
+#[derive(Debug, Clone, Serialize)]
 pub struct DevicesStepStats {
-    workload: f32,
+    /// Fraction of busy devices.
+    pub workload: f32,
 }
 
 impl Default for Devices {
@@ -28,68 +62,157 @@ impl Default for Devices {
 }
 
 impl Devices {
+    /// Creates `count` idle devices.
     pub fn new(count: usize) -> Self {
         Self {
             busy: 0,
+            blocked: 0,
             workers: vec![None; count],
-            workloads: Vec::new(),
+            workloads: TimeWeighted::new(0.0, Duration::ZERO),
+            blocked_loads: TimeWeighted::new(0.0, Duration::ZERO),
         }
     }
 
+    /// Records every change in workload in the history of `workloads`, which
+    /// otherwise keeps constant memory.
+    pub fn record_history(mut self) -> Self {
+        self.workloads = self.workloads.record_history();
+        self
+    }
+
+    /// Number of devices not holding an entity.
     pub fn idle(&self) -> usize {
         self.workers.len() - self.busy
     }
 
+    /// Number of devices, busy or idle.
     pub fn count(&self) -> usize {
         self.workers.len()
     }
 
-    pub fn load(&mut self, event_id: usize, simulation_duration: Duration) {
-        if self.workers.len() == self.busy {
-            panic!("all devices are busy");
-        }
+    /// Discards the statistics of workloads, restarting them from the current
+    /// workload at `simulation_duration`.
+    pub fn reset_stats(&mut self, simulation_duration: Duration) {
+        self.workloads.reset(simulation_duration);
+        self.blocked_loads.reset(simulation_duration);
+    }
+
+    /// Extends the workload and the blocked fraction until the end of the
+    /// simulation at `simulation_duration`, so that both cover the same time.
+    pub fn finish(&mut self, simulation_duration: Duration) {
+        self.workloads.advance_to(simulation_duration);
+        self.blocked_loads.advance_to(simulation_duration);
+    }
+
+    /// Marks one more busy device as blocked.
+    pub fn block(&mut self, simulation_duration: Duration) {
+        self.blocked += 1;
+        self.blocked_loads.set(
+            self.blocked as f64 / self.workers.len() as f64,
+            simulation_duration,
+        );
+    }
+
+    /// Marks one blocked device as no longer blocked.
+    pub fn unblock(&mut self, simulation_duration: Duration) {
+        self.blocked = self.blocked.saturating_sub(1);
+        self.blocked_loads.set(
+            self.blocked as f64 / self.workers.len() as f64,
+            simulation_duration,
+        );
+    }
+
+    /// Puts `entity_id` on the first idle device. Fails if every device is
+    /// busy.
+    pub fn load(
+        &mut self,
+        entity_id: usize,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
         let available_worker_idx = self
             .workers
             .iter()
             .position(|i| i.is_none())
-            .expect("some worker should be available");
-        self.workers[available_worker_idx] = Some(event_id);
+            .ok_or(SimulationError::AllDevicesBusy)?;
+        self.workers[available_worker_idx] = Some(entity_id);
         self.busy += 1;
-        self.workloads.push((simulation_duration, self.workload()));
+        self.workloads
+            .set(self.workload() as f64, simulation_duration);
+        Ok(())
     }
 
-    pub fn unload(&mut self, event_id: usize, simulation_duration: Duration) {
+    /// Frees the device holding `entity_id`. Fails if no device is busy or
+    /// none holds the entity.
+    pub fn unload(
+        &mut self,
+        entity_id: usize,
+        simulation_duration: Duration,
+    ) -> Result<(), SimulationError> {
         if self.busy == 0 {
-            panic!("no devices are busy");
+            return Err(SimulationError::NoDevicesBusy);
         }
-        let event_idx = self
+        let entity_idx = self
             .workers
             .iter()
-            .position(|i| i.is_some_and(|e| e == event_id))
-            .expect("event id to unload should be in the list of workers");
-        self.workers[event_idx] = None;
+            .position(|i| i.is_some_and(|e| e == entity_id))
+            .ok_or(SimulationError::NotInService(entity_id))?;
+        self.workers[entity_idx] = None;
         self.busy -= 1;
-        self.workloads.push((simulation_duration, self.workload()));
+        self.workloads
+            .set(self.workload() as f64, simulation_duration);
+        Ok(())
     }
 
+    /// Fraction of busy devices.
     pub fn workload(&self) -> f32 {
         self.busy as f32 / self.workers.len() as f32
     }
 }
 
 impl Stats for Devices {
-    fn stats(&self) -> Box<dyn Debug> {
-        Box::new(DevicesStats {
+    type Output = DevicesStats;
+
+    fn stats(&self) -> DevicesStats {
+        let workloads = self.workloads.stats();
+        let blocked_loads = self.blocked_loads.stats();
+        DevicesStats {
             final_workload: self.workload(),
-            average_workload: weighted_average(&self.workloads),
-        })
+            average_workload: workloads.average as f32,
+            workload_variance: workloads.variance as f32,
+            min_workload: workloads.min as f32,
+            max_workload: workloads.max as f32,
+            average_blocked: blocked_loads.average as f32,
+            blocked_time: (self.blocked_loads.area() * self.workers.len() as f64) as f32,
+        }
     }
 }
 
 impl StepStats for Devices {
-    fn step_stats(&self) -> Box<dyn Debug> {
-        Box::new(DevicesStepStats {
+    type Output = DevicesStepStats;
+
+    fn step_stats(&self) -> DevicesStepStats {
+        DevicesStepStats {
             workload: self.workload(),
-        })
+        }
+    }
+}
+
+impl Metrics for DevicesStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        vec![
+            ("final_workload".into(), self.final_workload as f64),
+            ("average_workload".into(), self.average_workload as f64),
+            ("workload_variance".into(), self.workload_variance as f64),
+            ("min_workload".into(), self.min_workload as f64),
+            ("max_workload".into(), self.max_workload as f64),
+            ("average_blocked".into(), self.average_blocked as f64),
+            ("blocked_time".into(), self.blocked_time as f64),
+        ]
+    }
+}
+
+impl Metrics for DevicesStepStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        vec![("workload".into(), self.workload as f64)]
     }
 }
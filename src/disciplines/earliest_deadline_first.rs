@@ -0,0 +1,40 @@
+use crate::{
+    disciplines::{highest_rank, lowest_rank, QueueDiscipline},
+    entity::Entity,
+    queue::QueueEntry,
+};
+use std::time::Duration;
+
+/// Serves the entity with the earliest deadline first. The deadline is read
+/// from a numeric attribute of the entity, in seconds of simulated time;
+/// entities without one are served last. Ties are served in order of arrival.
+#[derive(Debug, Clone)]
+pub struct EarliestDeadlineFirst {
+    attribute: String,
+}
+
+impl EarliestDeadlineFirst {
+    /// Reads deadlines from the attribute named `attribute`.
+    pub fn new(attribute: impl Into<String>) -> Self {
+        Self {
+            attribute: attribute.into(),
+        }
+    }
+}
+
+impl QueueDiscipline for EarliestDeadlineFirst {
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize {
+        lowest_rank(waiting)
+    }
+
+    fn last(&self, waiting: &[QueueEntry]) -> usize {
+        highest_rank(waiting)
+    }
+
+    fn rank(&self, entity: &Entity, _service_time: Option<Duration>) -> f64 {
+        entity
+            .get(&self.attribute)
+            .and_then(|value| value.as_f64())
+            .unwrap_or(f64::INFINITY)
+    }
+}
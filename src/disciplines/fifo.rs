@@ -0,0 +1,11 @@
+use crate::{disciplines::QueueDiscipline, queue::QueueEntry};
+
+/// Serves entities in order of arrival, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fifo;
+
+impl QueueDiscipline for Fifo {
+    fn select(&mut self, _waiting: &[QueueEntry]) -> usize {
+        0
+    }
+}
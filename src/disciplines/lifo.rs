@@ -0,0 +1,15 @@
+use crate::{disciplines::QueueDiscipline, queue::QueueEntry};
+
+/// Serves the most recent arrival first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lifo;
+
+impl QueueDiscipline for Lifo {
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize {
+        waiting.len() - 1
+    }
+
+    fn last(&self, _waiting: &[QueueEntry]) -> usize {
+        0
+    }
+}
@@ -0,0 +1,81 @@
+//! Orders in which a queue hands waiting entities to the devices.
+
+mod earliest_deadline_first;
+mod fifo;
+mod lifo;
+mod random_order;
+mod shortest_job_first;
+
+use crate::{entity::Entity, queue::QueueEntry};
+pub use earliest_deadline_first::EarliestDeadlineFirst;
+pub use fifo::Fifo;
+pub use lifo::Lifo;
+pub use random_order::RandomOrder;
+pub use shortest_job_first::ShortestJobFirst;
+use std::time::Duration;
+
+/// Chooses which waiting entity a [`Queue`](crate::queue::Queue) serves next.
+pub trait QueueDiscipline: Send {
+    /// Index of the entry to serve next in `waiting`, which holds the
+    /// candidates in order of arrival and is never empty.
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize;
+    /// Index of the entry that would be served last in `waiting`, e.g. to
+    /// pick an entity leaving for a shorter queue. Defaults to the most
+    /// recent arrival.
+    fn last(&self, waiting: &[QueueEntry]) -> usize {
+        waiting.len() - 1
+    }
+    /// Rank stored with `entity` when it joins the queue, for disciplines
+    /// serving by rank. `service_time` is only sampled in advance when
+    /// [`QueueDiscipline::presamples_service_time`] holds.
+    fn rank(&self, _entity: &Entity, _service_time: Option<Duration>) -> f64 {
+        0.0
+    }
+    /// Whether service times are sampled when entities join the queue rather
+    /// than when their service starts.
+    fn presamples_service_time(&self) -> bool {
+        false
+    }
+    /// Reseeds the random stream of the discipline, if it has one.
+    fn seed(&mut self, _seed: u64) {}
+}
+
+impl<Q: QueueDiscipline + ?Sized> QueueDiscipline for Box<Q> {
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize {
+        (**self).select(waiting)
+    }
+
+    fn last(&self, waiting: &[QueueEntry]) -> usize {
+        (**self).last(waiting)
+    }
+
+    fn rank(&self, entity: &Entity, service_time: Option<Duration>) -> f64 {
+        (**self).rank(entity, service_time)
+    }
+
+    fn presamples_service_time(&self) -> bool {
+        (**self).presamples_service_time()
+    }
+
+    fn seed(&mut self, seed: u64) {
+        (**self).seed(seed)
+    }
+}
+
+/// Index of the first entry with the lowest rank.
+fn lowest_rank(waiting: &[QueueEntry]) -> usize {
+    waiting
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.rank.total_cmp(&b.rank))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Index of the last entry with the highest rank.
+fn highest_rank(waiting: &[QueueEntry]) -> usize {
+    waiting
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.rank.total_cmp(&b.rank))
+        .map_or(0, |(index, _)| index)
+}
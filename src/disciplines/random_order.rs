@@ -0,0 +1,37 @@
+use crate::{
+    disciplines::QueueDiscipline,
+    queue::QueueEntry,
+    random::{unseeded_rng, SimulationRng},
+};
+use rand::{Rng, SeedableRng};
+
+/// Serves a waiting entity chosen uniformly at random.
+pub struct RandomOrder {
+    rng: SimulationRng,
+}
+
+impl RandomOrder {
+    /// Creates the discipline with an unseeded stream, replaced when the
+    /// network seeds its blocks.
+    pub fn new() -> Self {
+        Self {
+            rng: unseeded_rng(),
+        }
+    }
+}
+
+impl Default for RandomOrder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueueDiscipline for RandomOrder {
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize {
+        self.rng.random_range(0..waiting.len())
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = SimulationRng::seed_from_u64(seed);
+    }
+}
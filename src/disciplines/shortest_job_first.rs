@@ -0,0 +1,29 @@
+use crate::{
+    disciplines::{highest_rank, lowest_rank, QueueDiscipline},
+    entity::Entity,
+    queue::QueueEntry,
+};
+use std::time::Duration;
+
+/// Serves the entity with the shortest service time first, sampling service
+/// times when entities join the queue. Ties are served in order of arrival.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShortestJobFirst;
+
+impl QueueDiscipline for ShortestJobFirst {
+    fn select(&mut self, waiting: &[QueueEntry]) -> usize {
+        lowest_rank(waiting)
+    }
+
+    fn last(&self, waiting: &[QueueEntry]) -> usize {
+        highest_rank(waiting)
+    }
+
+    fn rank(&self, _entity: &Entity, service_time: Option<Duration>) -> f64 {
+        service_time.map_or(f64::INFINITY, |time| time.as_secs_f64())
+    }
+
+    fn presamples_service_time(&self) -> bool {
+        true
+    }
+}
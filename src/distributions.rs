@@ -1,10 +1,16 @@
+//! Distributions of inter-arrival and service times not provided by
+//! `rand_distr`, and a wrapper over every distribution a model can use.
+
 use rand::{distr::Distribution, Rng};
+use rand_distr::{Exp, Gamma, LogNormal, Normal, Triangular, Uniform};
 
+/// A distribution that always yields the same value.
 pub struct Deterministic {
     value: f32,
 }
 
 impl Deterministic {
+    /// Always yields `value`.
     pub fn new(value: f32) -> Deterministic {
         Deterministic { value }
     }
@@ -15,3 +21,36 @@ impl Distribution<f32> for Deterministic {
         self.value
     }
 }
+
+/// One of the supported distributions, chosen at runtime, e.g. by a
+/// [`Model`](crate::model::Model).
+pub enum AnyDistribution {
+    /// A constant value.
+    Deterministic(Deterministic),
+    /// Exponential distribution, e.g. of Poisson inter-arrival times.
+    Exponential(Exp<f32>),
+    /// Uniform distribution over an interval.
+    Uniform(Uniform<f32>),
+    /// Normal distribution.
+    Normal(Normal<f32>),
+    /// Log-normal distribution.
+    LogNormal(LogNormal<f32>),
+    /// Gamma distribution, also used for Erlang distributions.
+    Gamma(Gamma<f32>),
+    /// Triangular distribution.
+    Triangular(Triangular<f32>),
+}
+
+impl Distribution<f32> for AnyDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        match self {
+            Self::Deterministic(distribution) => distribution.sample(rng),
+            Self::Exponential(distribution) => distribution.sample(rng),
+            Self::Uniform(distribution) => distribution.sample(rng),
+            Self::Normal(distribution) => distribution.sample(rng),
+            Self::LogNormal(distribution) => distribution.sample(rng),
+            Self::Gamma(distribution) => distribution.sample(rng),
+            Self::Triangular(distribution) => distribution.sample(rng),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Entities moving through the network and the attributes they carry.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map, HashMap},
+    time::Duration,
+};
+
+/// Value of an entity attribute.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    /// A flag.
+    Bool(bool),
+    /// An integer.
+    Int(i64),
+    /// A float.
+    Float(f64),
+    /// A string.
+    Text(String),
+}
+
+/// A customer travelling through the network, with data that blocks and
+/// routers can read and write on the way.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entity {
+    /// Identifier, unique among the entities in the network.
+    pub id: usize,
+    /// Type of the entity, e.g. to route different customers differently.
+    pub kind: Option<String>,
+    /// Priority class, higher is more urgent.
+    pub priority: i32,
+    /// Simulated time the entity was created at.
+    pub created_at: Duration,
+    /// Named values set at creation or along the way.
+    pub attributes: HashMap<String, Value>,
+}
+
+/// Every entity currently in the network, keyed by id.
+///
+/// Entities are added by the blocks creating them and removed by the blocks
+/// disposing of them, or by the network when a router sends them nowhere.
+#[derive(Debug, Default)]
+pub struct Entities {
+    entities: HashMap<usize, Entity>,
+    next_id: usize,
+}
+
+impl Value {
+    /// The value if it is a flag.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value if it is an integer.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value as a float, converting integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::Float(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::Text(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl Entity {
+    /// Creates an entity of no kind with priority 0 and no attributes.
+    pub fn new(id: usize, created_at: Duration) -> Self {
+        Self {
+            id,
+            kind: None,
+            priority: 0,
+            created_at,
+            attributes: HashMap::new(),
+        }
+    }
+
+    /// Value of the attribute `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.attributes.get(key)
+    }
+
+    /// Sets the attribute `key`, returning its previous value.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        self.attributes.insert(key.into(), value.into())
+    }
+}
+
+impl Entities {
+    /// Creates an empty set of entities.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a new entity created at `created_at`, with an id not used by any
+    /// other entity in the network.
+    pub fn create(&mut self, created_at: Duration) -> &mut Entity {
+        while self.entities.contains_key(&self.next_id) {
+            self.next_id += 1;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entities
+            .entry(id)
+            .or_insert_with(|| Entity::new(id, created_at))
+    }
+
+    /// Adds `entity`, replacing any entity with the same id.
+    pub fn insert(&mut self, entity: Entity) {
+        self.entities.insert(entity.id, entity);
+    }
+
+    /// The entity `id`, if in the network.
+    pub fn get(&self, id: usize) -> Option<&Entity> {
+        self.entities.get(&id)
+    }
+
+    /// The entity `id` for changing, if in the network.
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Entity> {
+        self.entities.get_mut(&id)
+    }
+
+    /// Takes the entity `id` out of the network.
+    pub fn remove(&mut self, id: usize) -> Option<Entity> {
+        self.entities.remove(&id)
+    }
+
+    /// Whether the entity `id` is in the network.
+    pub fn contains(&self, id: usize) -> bool {
+        self.entities.contains_key(&id)
+    }
+
+    /// Number of entities in the network.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Whether the network holds no entity.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Every entity in the network, in no particular order.
+    pub fn iter(&self) -> hash_map::Values<'_, usize, Entity> {
+        self.entities.values()
+    }
+}
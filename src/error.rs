@@ -0,0 +1,196 @@
+//! Errors of simulations, topology checks and replications.
+
+use crate::blocks::BlockId;
+use rayon::ThreadPoolBuildError;
+use std::{error::Error, fmt, io, time::Duration};
+
+/// Error aborting a simulation, caused either by a misconfigured network or by
+/// a violated internal invariant.
+#[derive(Debug)]
+pub enum SimulationError {
+    /// An event was scheduled for a block that is not part of the network.
+    UnknownBlock(BlockId),
+    /// An event refers to an entity that is not in the network.
+    UnknownEntity(usize),
+    /// A delay sampled by a block is negative, infinite or NaN.
+    InvalidDelay {
+        /// Block that sampled the delay.
+        block: BlockId,
+        /// The delay, in seconds.
+        value: f32,
+    },
+    /// An entity was loaded into a device pool with no idle device.
+    AllDevicesBusy,
+    /// An entity was unloaded from a device pool with no busy device.
+    NoDevicesBusy,
+    /// An entity was unloaded from a device pool that is not serving it.
+    NotInService(usize),
+    /// An entity was dequeued from an empty queue.
+    EmptyQueue,
+    /// An entity was moved into the full queue of the block.
+    QueueFull(BlockId),
+    /// The warm-up period does not end before the simulation does.
+    WarmUpTooLong {
+        /// Length of the warm-up period.
+        warm_up: Duration,
+        /// Length of the simulation.
+        duration: Duration,
+    },
+    /// The network failed [`QueueNetwork::validate`](crate::network::QueueNetwork::validate).
+    Invalid(Vec<ValidationError>),
+    /// Reading from stdin failed while stepping through the simulation.
+    Io(io::Error),
+}
+
+/// Error aborting [`Replications::run`](crate::replication::Replications::run).
+#[derive(Debug)]
+pub enum ReplicationError {
+    /// A replication failed.
+    Simulation(SimulationError),
+    /// The thread pool running replications could not be started.
+    ThreadPool(ThreadPoolBuildError),
+    /// No replication was requested.
+    NoReplications,
+    /// The confidence level is not strictly between 0 and 1.
+    InvalidConfidence(f64),
+}
+
+/// Topology problem found by
+/// [`QueueNetwork::validate`](crate::network::QueueNetwork::validate).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// A block with this id was added more than once; only the last one is
+    /// kept.
+    DuplicateBlock(BlockId),
+    /// A block routes to a block that is not part of the network.
+    DanglingTarget {
+        /// Block whose router names the missing block.
+        block: BlockId,
+        /// The missing block.
+        target: BlockId,
+    },
+    /// No path leads to the block from any create block.
+    Unreachable(BlockId),
+    /// No path leads from the process block to any dispose block.
+    NoPathToDispose(BlockId),
+    /// A probability router has a zero, negative or non-finite weight.
+    InvalidWeight {
+        /// Block of the router.
+        block: BlockId,
+        /// Block the weight leads to.
+        target: BlockId,
+        /// The weight.
+        weight: f32,
+    },
+    /// A block of a jockeying group is not part of the network or has no
+    /// queue.
+    NotJockeyable(BlockId),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownBlock(block) => write!(f, "unknown block `{block}`"),
+            Self::InvalidDelay { block, value } => {
+                write!(f, "block `{block}` sampled an invalid delay of {value}")
+            }
+            Self::AllDevicesBusy => write!(f, "all devices are busy"),
+            Self::NoDevicesBusy => write!(f, "no devices are busy"),
+            Self::UnknownEntity(entity_id) => write!(f, "unknown entity {entity_id}"),
+            Self::NotInService(entity_id) => write!(f, "entity {entity_id} is not in service"),
+            Self::EmptyQueue => write!(f, "queue is empty"),
+            Self::QueueFull(block) => write!(f, "queue of block `{block}` is full"),
+            Self::WarmUpTooLong { warm_up, duration } => write!(
+                f,
+                "warm-up of {}s does not end before the simulation duration of {}s",
+                warm_up.as_secs_f64(),
+                duration.as_secs_f64()
+            ),
+            Self::Invalid(errors) => {
+                write!(f, "invalid network: ")?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{error}")?;
+                }
+                Ok(())
+            }
+            Self::Io(error) => write!(f, "failed to read from stdin: {error}"),
+        }
+    }
+}
+
+impl Error for SimulationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ReplicationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Simulation(error) => write!(f, "{error}"),
+            Self::ThreadPool(error) => write!(f, "failed to start thread pool: {error}"),
+            Self::NoReplications => write!(f, "at least one replication is needed"),
+            Self::InvalidConfidence(confidence) => {
+                write!(f, "confidence level {confidence} is not between 0 and 1")
+            }
+        }
+    }
+}
+
+impl Error for ReplicationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Simulation(error) => error.source(),
+            Self::ThreadPool(error) => Some(error),
+            Self::NoReplications | Self::InvalidConfidence(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateBlock(block) => write!(f, "block `{block}` is added more than once"),
+            Self::DanglingTarget { block, target } => {
+                write!(f, "block `{block}` routes to unknown block `{target}`")
+            }
+            Self::Unreachable(block) => {
+                write!(f, "block `{block}` is unreachable from any create block")
+            }
+            Self::NoPathToDispose(block) => {
+                write!(f, "block `{block}` can never reach a dispose block")
+            }
+            Self::InvalidWeight {
+                block,
+                target,
+                weight,
+            } => write!(
+                f,
+                "block `{block}` routes to `{target}` with invalid weight {weight}"
+            ),
+            Self::NotJockeyable(block) => {
+                write!(f, "block `{block}` of a jockeying group has no queue")
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl From<io::Error> for SimulationError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<SimulationError> for ReplicationError {
+    fn from(error: SimulationError) -> Self {
+        Self::Simulation(error)
+    }
+}
@@ -0,0 +1,256 @@
+//! Point estimates and Student-t confidence intervals of means.
+
+use serde::Serialize;
+
+/// Point estimate of a mean with a Student-t confidence interval.
+#[derive(Debug, Clone, Serialize)]
+pub struct Estimate {
+    /// Number of samples.
+    pub count: usize,
+    /// Sample mean.
+    pub mean: f64,
+    /// Sample standard deviation, NaN with fewer than two samples.
+    pub std_dev: f64,
+    /// Half width of the confidence interval around the mean, NaN with fewer
+    /// than two samples.
+    pub half_width: f64,
+    /// Confidence level of the interval, e.g. 0.95.
+    pub confidence: f64,
+}
+
+impl Estimate {
+    /// Estimates the mean of independent, identically distributed samples.
+    pub fn from_samples(samples: &[f64], confidence: f64) -> Self {
+        let count = samples.len();
+        let mean = samples.iter().sum::<f64>() / count as f64;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / (count as f64 - 1.0);
+        let std_dev = if count > 1 { variance.sqrt() } else { f64::NAN };
+        let half_width = if count > 1 {
+            student_t_quantile((1.0 + confidence) / 2.0, count as f64 - 1.0) * std_dev
+                / (count as f64).sqrt()
+        } else {
+            f64::NAN
+        };
+        Self {
+            count,
+            mean,
+            std_dev,
+            half_width,
+            confidence,
+        }
+    }
+
+    /// Lower bound of the confidence interval.
+    pub fn lower(&self) -> f64 {
+        self.mean - self.half_width
+    }
+
+    /// Upper bound of the confidence interval.
+    pub fn upper(&self) -> f64 {
+        self.mean + self.half_width
+    }
+}
+
+/// Quantile of the Student-t distribution with `degrees_of_freedom`, i.e. the
+/// `t` for which `P(T <= t) = probability`.
+pub fn student_t_quantile(probability: f64, degrees_of_freedom: f64) -> f64 {
+    if !(0.0..=1.0).contains(&probability) || degrees_of_freedom <= 0.0 {
+        return f64::NAN;
+    }
+    if probability < 0.5 {
+        return -student_t_quantile(1.0 - probability, degrees_of_freedom);
+    }
+    let mut high = 1.0;
+    while student_t_cdf(high, degrees_of_freedom) < probability {
+        high *= 2.0;
+        if high.is_infinite() {
+            return f64::INFINITY;
+        }
+    }
+    let mut low = 0.0;
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if student_t_cdf(middle, degrees_of_freedom) < probability {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Cumulative distribution function of the Student-t distribution.
+pub fn student_t_cdf(t: f64, degrees_of_freedom: f64) -> f64 {
+    let tail = 0.5
+        * regularized_incomplete_beta(
+            degrees_of_freedom / 2.0,
+            0.5,
+            degrees_of_freedom / (degrees_of_freedom + t * t),
+        );
+    if t >= 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+fn regularized_incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly for x < (a + 1) / (a + b + 2).
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut fraction = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            fraction *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    fraction
+}
+
+/// Natural logarithm of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, coefficient)| {
+            sum + coefficient / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn student_t_quantiles_match_tables() {
+        for (probability, degrees_of_freedom, expected) in [
+            (0.975, 1.0, 12.7062),
+            (0.975, 9.0, 2.2622),
+            (0.975, 30.0, 2.0423),
+            (0.95, 5.0, 2.0150),
+            (0.995, 10.0, 3.1693),
+            (0.9, 2.0, 1.8856),
+            (0.5, 4.0, 0.0),
+        ] {
+            assert_close(
+                student_t_quantile(probability, degrees_of_freedom),
+                expected,
+                1e-4,
+            );
+        }
+        assert_close(student_t_quantile(0.025, 9.0), -2.2622, 1e-4);
+        assert!(student_t_quantile(1.5, 9.0).is_nan());
+        assert!(student_t_quantile(0.975, 0.0).is_nan());
+    }
+
+    #[test]
+    fn student_t_cdf_is_symmetric() {
+        assert_close(student_t_cdf(0.0, 3.0), 0.5, 1e-12);
+        assert_close(student_t_cdf(2.2622, 9.0), 0.975, 1e-5);
+        assert_close(student_t_cdf(-2.2622, 9.0), 0.025, 1e-5);
+    }
+
+    #[test]
+    fn incomplete_beta_matches_closed_forms() {
+        for x in [0.1, 0.3, 0.5, 0.8] {
+            assert_close(regularized_incomplete_beta(1.0, 1.0, x), x, 1e-12);
+            assert_close(regularized_incomplete_beta(2.0, 1.0, x), x * x, 1e-12);
+            assert_close(
+                regularized_incomplete_beta(1.0, 3.0, x),
+                1.0 - (1.0 - x).powi(3),
+                1e-12,
+            );
+        }
+        assert_close(regularized_incomplete_beta(4.5, 4.5, 0.5), 0.5, 1e-12);
+        assert_eq!(regularized_incomplete_beta(2.0, 3.0, 0.0), 0.0);
+        assert_eq!(regularized_incomplete_beta(2.0, 3.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn ln_gamma_matches_known_values() {
+        let pi = std::f64::consts::PI;
+        assert_close(ln_gamma(1.0), 0.0, 1e-12);
+        assert_close(ln_gamma(2.0), 0.0, 1e-12);
+        assert_close(ln_gamma(5.0), 24.0_f64.ln(), 1e-12);
+        assert_close(ln_gamma(0.5), pi.sqrt().ln(), 1e-12);
+        assert_close(ln_gamma(0.25), 3.625_609_908_221_908_f64.ln(), 1e-12);
+        assert_close(ln_gamma(100.0), 359.134_205_369_575_4, 1e-9);
+    }
+
+    #[test]
+    fn estimate_of_one_to_ten() {
+        let samples = (1..=10).map(f64::from).collect::<Vec<_>>();
+        let estimate = Estimate::from_samples(&samples, 0.95);
+        assert_eq!(estimate.count, 10);
+        assert_eq!(estimate.mean, 5.5);
+        assert_close(estimate.std_dev, 3.027_650_354_097_491_7, 1e-12);
+        assert_close(estimate.half_width, 2.1659, 1e-4);
+        assert!(Estimate::from_samples(&[1.0], 0.95).half_width.is_nan());
+    }
+}
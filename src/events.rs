@@ -1,34 +1,310 @@
+//! Future events of a simulation, ordered by time.
+
 use crate::blocks::BlockId;
-use std::{cmp::Ordering, time::Duration};
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    time::Duration,
+};
 
-#[derive(Debug, Copy, Clone)]
+/// Kind of an [`Event`]: an entity arriving at or leaving a block, or a
+/// deadline a block set for an entity, e.g. its patience in a queue.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
 pub enum EventType {
+    /// The entity arrives at the block.
     In,
+    /// The entity is done at the block, e.g. its service ended.
     Out,
+    /// A deadline the block set for the entity has passed.
+    Timeout,
 }
 
+/// A scheduled event: simulation time, target block, event type and entity id.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Event(pub Duration, pub BlockId, pub EventType, pub usize);
 
-impl PartialEq<Event> for Event {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+/// Priority of simultaneous events. Events with a higher priority run first.
+pub type EventPriority = Box<dyn Fn(&Event) -> i32 + Send>;
+
+/// Identifies a scheduled event, to cancel or reschedule it while pending.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EventHandle(u64);
+
+struct ScheduledEvent {
+    event: Event,
+    priority: i32,
+    sequence: u64,
+    handle: EventHandle,
+}
+
+/// Future event list.
+///
+/// Events are popped in order of time, then of descending priority, then of
+/// insertion. Without a priority function every event has priority 0, so
+/// simultaneous events run in the order they were scheduled and a run only
+/// depends on the network seed.
+///
+/// Cancelled and rescheduled events are deleted lazily: their stale heap
+/// entries are skipped when they reach the top, and dropped in bulk once they
+/// outnumber the pending events.
+pub struct EventQueue {
+    heap: BinaryHeap<ScheduledEvent>,
+    /// Every pending event, with the sequence number of its live heap entry.
+    pending: HashMap<EventHandle, (u64, Event)>,
+    priority: Option<EventPriority>,
+    sequence: u64,
+    next_handle: u64,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl Eq for Event {
-    fn assert_receiver_is_total_eq(&self) {
-        // This is a no-op because we know that `Instant` is `Eq`
+impl EventQueue {
+    /// Creates an empty event list in which simultaneous events run in the
+    /// order they were scheduled.
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            pending: HashMap::new(),
+            priority: None,
+            sequence: 0,
+            next_handle: 0,
+        }
+    }
+
+    /// Creates an empty event list in which simultaneous events run in order
+    /// of descending `priority`, then in the order they were scheduled.
+    pub fn with_priority(priority: impl Fn(&Event) -> i32 + Send + 'static) -> Self {
+        Self {
+            priority: Some(Box::new(priority)),
+            ..Self::new()
+        }
+    }
+
+    /// Schedules `event`, returning a handle to cancel or reschedule it.
+    pub fn push(&mut self, event: Event) -> EventHandle {
+        let handle = EventHandle(self.next_handle);
+        self.next_handle += 1;
+        self.schedule(event, handle);
+        handle
+    }
+
+    /// Removes the pending event of `handle`, returning it. `None` if it
+    /// already ran or was cancelled.
+    pub fn cancel(&mut self, handle: EventHandle) -> Option<Event> {
+        let (_, event) = self.pending.remove(&handle)?;
+        self.compact();
+        Some(event)
     }
+
+    /// Moves the pending event of `handle` to `time`, keeping its handle.
+    /// Among simultaneous events it runs as if scheduled now. Returns whether
+    /// the event was still pending.
+    pub fn reschedule(&mut self, handle: EventHandle, time: Duration) -> bool {
+        let Some(&event) = self.get(handle) else {
+            return false;
+        };
+        self.schedule(Event(time, event.1, event.2, event.3), handle);
+        self.compact();
+        true
+    }
+
+    /// The pending event of `handle`, if any.
+    pub fn get(&self, handle: EventHandle) -> Option<&Event> {
+        self.pending.get(&handle).map(|(_, event)| event)
+    }
+
+    /// Removes and returns the next event to run, if any.
+    pub fn pop(&mut self) -> Option<Event> {
+        self.skip_stale();
+        let scheduled = self.heap.pop()?;
+        self.pending.remove(&scheduled.handle);
+        Some(scheduled.event)
+    }
+
+    /// The next event to run, if any, without removing it. Takes `&mut self`
+    /// to drop the stale entries in front of it.
+    pub fn peek(&mut self) -> Option<&Event> {
+        self.skip_stale();
+        self.heap.peek().map(|scheduled| &scheduled.event)
+    }
+
+    /// Number of pending events.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Whether no event is pending.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn schedule(&mut self, event: Event, handle: EventHandle) {
+        let priority = self
+            .priority
+            .as_ref()
+            .map_or(0, |priority| priority(&event));
+        self.heap.push(ScheduledEvent {
+            event,
+            priority,
+            sequence: self.sequence,
+            handle,
+        });
+        self.pending.insert(handle, (self.sequence, event));
+        self.sequence += 1;
+    }
+
+    /// Drops stale entries from the top of the heap.
+    fn skip_stale(&mut self) {
+        while self
+            .heap
+            .peek()
+            .is_some_and(|scheduled| !is_live(&self.pending, scheduled))
+        {
+            self.heap.pop();
+        }
+    }
+
+    /// Drops every stale entry once they make up most of the heap, keeping
+    /// memory proportional to the pending events.
+    fn compact(&mut self) {
+        if self.heap.len() > 2 * self.pending.len() + 64 {
+            let pending = &self.pending;
+            self.heap.retain(|scheduled| is_live(pending, scheduled));
+        }
+    }
+}
+
+/// Whether `scheduled` is the current entry of a pending event rather than
+/// one left behind by a cancellation or rescheduling.
+fn is_live(pending: &HashMap<EventHandle, (u64, Event)>, scheduled: &ScheduledEvent) -> bool {
+    pending
+        .get(&scheduled.handle)
+        .is_some_and(|(sequence, _)| *sequence == scheduled.sequence)
 }
 
-impl PartialOrd<Event> for Event {
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+impl PartialOrd for ScheduledEvent {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for Event {
+impl Ord for ScheduledEvent {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.0.cmp(&other.0).reverse()
+        self.event
+            .0
+            .cmp(&other.event.0)
+            .reverse()
+            .then(self.priority.cmp(&other.priority))
+            .then(self.sequence.cmp(&other.sequence).reverse())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: u64, entity_id: usize) -> Event {
+        Event(
+            Duration::from_secs(seconds),
+            "block",
+            EventType::In,
+            entity_id,
+        )
+    }
+
+    #[test]
+    fn simultaneous_events_run_by_priority_then_insertion() {
+        let mut events = EventQueue::with_priority(|event| match event.2 {
+            EventType::Out => 1,
+            _ => 0,
+        });
+        let out = |entity_id| Event(Duration::from_secs(1), "block", EventType::Out, entity_id);
+        events.push(at(1, 0));
+        events.push(out(1));
+        events.push(at(0, 2));
+        events.push(at(1, 3));
+        events.push(out(4));
+        assert_eq!(events.pop(), Some(at(0, 2)));
+        assert_eq!(events.pop(), Some(out(1)));
+        assert_eq!(events.pop(), Some(out(4)));
+        assert_eq!(events.pop(), Some(at(1, 0)));
+        assert_eq!(events.pop(), Some(at(1, 3)));
+    }
+
+    #[test]
+    fn cancelled_event_is_never_popped() {
+        let mut events = EventQueue::new();
+        let first = events.push(at(1, 0));
+        events.push(at(2, 1));
+        assert_eq!(events.cancel(first), Some(at(1, 0)));
+        assert_eq!(events.cancel(first), None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.peek(), Some(&at(2, 1)));
+        assert_eq!(events.pop(), Some(at(2, 1)));
+        assert_eq!(events.pop(), None);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn rescheduled_event_runs_once_at_its_new_time() {
+        let mut events = EventQueue::new();
+        let early = events.push(at(3, 0));
+        let late = events.push(at(4, 1));
+        events.push(at(5, 2));
+        assert!(events.reschedule(early, Duration::from_secs(6)));
+        assert!(events.reschedule(late, Duration::from_secs(1)));
+        assert_eq!(events.get(early), Some(&at(6, 0)));
+        assert_eq!(events.len(), 3);
+        assert_eq!(events.pop(), Some(at(1, 1)));
+        assert_eq!(events.pop(), Some(at(5, 2)));
+        assert_eq!(events.pop(), Some(at(6, 0)));
+        assert_eq!(events.pop(), None);
+        assert!(!events.reschedule(early, Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn rescheduled_event_runs_after_simultaneous_ones() {
+        let mut events = EventQueue::new();
+        let handle = events.push(at(2, 0));
+        events.push(at(1, 1));
+        events.reschedule(handle, Duration::from_secs(1));
+        assert_eq!(events.pop(), Some(at(1, 1)));
+        assert_eq!(events.pop(), Some(at(1, 0)));
+    }
+
+    #[test]
+    fn compaction_drops_stale_entries() {
+        let mut events = EventQueue::new();
+        let handles: Vec<_> = (0..200).map(|i| events.push(at(i, i as usize))).collect();
+        for &handle in &handles[..150] {
+            events.cancel(handle);
+        }
+        assert_eq!(events.len(), 50);
+        assert!(events.heap.len() <= 2 * events.pending.len() + 64);
+        let kept = handles[199];
+        for time in 0..200 {
+            events.reschedule(kept, Duration::from_secs(300 + time));
+        }
+        assert!(events.heap.len() <= 2 * events.pending.len() + 64);
+        let popped: Vec<_> = std::iter::from_fn(|| events.pop()).collect();
+        assert_eq!(popped.len(), 50);
+        assert_eq!(
+            popped[..49],
+            (150..199).map(|i| at(i, i as usize)).collect::<Vec<_>>()
+        );
+        assert_eq!(popped[49], at(499, 199));
     }
 }
@@ -0,0 +1,29 @@
+//! Discrete-event simulation of queueing networks.
+//!
+//! A [`QueueNetwork`](network::QueueNetwork) is assembled from blocks that
+//! generate ([`CreateBlock`](blocks::CreateBlock)), serve
+//! ([`ProcessBlock`](blocks::ProcessBlock)) and absorb
+//! ([`DisposeBlock`](blocks::DisposeBlock)) events. Blocks are connected with
+//! [`routers`], and every block reports its state through the [`stats`]
+//! traits.
+
+#![warn(missing_docs)]
+
+pub mod batch_means;
+pub mod blocks;
+pub mod devices;
+pub mod disciplines;
+pub mod distributions;
+pub mod entity;
+pub mod error;
+pub mod estimation;
+pub mod events;
+pub mod model;
+pub mod network;
+pub mod queue;
+pub mod random;
+pub mod replication;
+pub mod routers;
+pub mod samples;
+pub mod stats;
+pub mod time_weighted;
@@ -1,80 +1,457 @@
-mod blocks;
-mod devices;
-mod distributions;
-mod events;
-mod network;
-mod queue;
-mod routers;
-mod stats;
-mod weighted_average;
-
-use crate::{
-    blocks::{CreateBlock, DisposeBlock, ProcessBlock},
-    devices::Devices,
+use clap::{builder::RangedU64ValueParser, Parser, ValueEnum};
+use queue_network::{
+    blocks::{BlockId, BlockStats},
     events::Event,
+    model::Model,
     network::QueueNetwork,
-    queue::Queue,
-    routers::{DirectRouter, ProbabilityRouter},
+    replication::{ReplicationReport, ReplicationRun, Replications},
+    stats::Metrics,
 };
-use rand_distr::Exp;
-use std::time::Duration;
-
-fn main() {
-    let mut network = QueueNetwork::new()
-        .add_block(
-            CreateBlock::builder("create")
-                .distribution(Exp::new(0.5).unwrap())
-                .router(DirectRouter::new("process1"))
-                .build(),
-        )
-        .add_block(
-            ProcessBlock::builder("process1")
-                .distribution(Exp::new(1.0).unwrap())
-                .queue(Queue::from_capacity(5))
-                .router(DirectRouter::new("process2"))
-                .build(),
-        )
-        .add_block(
-            ProcessBlock::builder("process2")
-                .distribution(Exp::new(1.0).unwrap())
-                .queue(Queue::from_capacity(5))
-                .devices(Devices::new(2))
-                .router(DirectRouter::new("process3"))
-                .build(),
-        )
-        .add_block(
-            ProcessBlock::builder("process3")
-                .distribution(Exp::new(1.0).unwrap())
-                .queue(Queue::from_capacity(5))
-                .router(ProbabilityRouter::new(&[
-                    (0.5, "process2"),
-                    (0.5, "dispose"),
-                ]))
-                .build(),
-        )
-        .add_block(DisposeBlock::new("dispose"))
-        .on_simulation_step(|network, Event(time, block_id, event_type, id)| {
-            let block = network.blocks.get(block_id).unwrap();
-            println!(
-                "Elapsed Time: {:.3} | Event: {:?} | Id: {} | {}: {:#?}",
-                time.as_secs_f32(),
-                event_type,
-                id,
-                block_id,
-                block.step_stats()
-            );
+use rand::{rng, Rng};
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Simulates a queueing network described by a model file.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Model file, in TOML or JSON format
+    model: PathBuf,
+    /// Simulated time, in seconds
+    #[arg(short, long, default_value = "1000", value_parser = parse_duration)]
+    duration: Duration,
+    /// Simulated time, in seconds, after which statistics are reset
+    #[arg(short, long, value_parser = parse_duration)]
+    warm_up: Option<Duration>,
+    /// Seed of the first replication, overriding the seed of the model
+    #[arg(short, long)]
+    seed: Option<u64>,
+    /// Number of independent replications
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    replications: usize,
+    /// Number of replications simulated in parallel, 0 for every core. By
+    /// default every core is used, unless the simulation is paced or traced
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Confidence level of the intervals summarizing several replications and
+    /// of batch means
+    #[arg(short, long, default_value_t = 0.95, value_parser = parse_confidence)]
+    confidence: f64,
+    /// Pace the simulation at one simulated second per real second
+    #[arg(long, conflicts_with_all = ["speed", "step_through"])]
+    real_time: bool,
+    /// Pace the simulation at this many simulated seconds per real second
+    #[arg(long, conflicts_with = "step_through")]
+    speed: Option<f32>,
+    /// Wait for a line on stdin before processing each event
+    #[arg(long)]
+    step_through: bool,
+    /// Also report steady-state confidence intervals of every replication by
+    /// the method of batch means
+    #[arg(short, long)]
+    batch_means: bool,
+    /// Format of the final statistics and the event trace
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// File receiving the final statistics, stdout by default
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// File receiving a line per processed event, `-` for stdout
+    #[arg(short, long)]
+    trace: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Human-readable text
+    Text,
+    /// A JSON object per line
+    Json,
+    /// A `block,metric,value` row per metric, prefixed by the replication or
+    /// event. Summary rows name the statistic instead of the replication
+    Csv,
+}
+
+/// Event trace shared with the simulation step callback, which cannot return
+/// errors itself.
+struct Trace {
+    writer: Mutex<Box<dyn Write + Send>>,
+    error: Mutex<Option<io::Error>>,
+}
+
+fn parse_confidence(value: &str) -> Result<f64, String> {
+    let confidence = value.parse::<f64>().map_err(|error| error.to_string())?;
+    if confidence > 0.0 && confidence < 1.0 {
+        Ok(confidence)
+    } else {
+        Err(format!("{confidence} is not between 0 and 1"))
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let model = Model::load(&args.model)?;
+    let base_seed = args.seed.or(model.seed).unwrap_or_else(|| rng().random());
+    let trace = args
+        .trace
+        .as_ref()
+        .map(|path| -> io::Result<_> {
+            let mut writer = create_writer(Some(path))?;
+            if let Format::Csv = args.format {
+                writeln!(writer, "time,event,id,block,metric,value")?;
+            }
+            Ok(Arc::new(Trace {
+                writer: Mutex::new(writer),
+                error: Mutex::new(None),
+            }))
+        })
+        .transpose()?;
+    let mut output = create_writer(args.output.as_ref())?;
+    if let Format::Csv = args.format {
+        writeln!(output, "replication,seed,block,metric,value")?;
+    }
+
+    // Building a model is deterministic, so only the first build can fail.
+    model.build()?;
+    // Paced or traced replications are only readable one at a time.
+    let sequential = args.real_time || args.speed.is_some() || args.step_through || trace.is_some();
+    let report = Replications::new(args.replications)
+        .seed(base_seed)
+        .confidence(args.confidence)
+        .threads(args.jobs.unwrap_or(if sequential { 1 } else { 0 }))
+        .run(
+            || {
+                let network = model.build().expect("model was built before");
+                configure(network, &args, trace.clone())
+            },
+            args.duration,
+        )?;
+    if let Some(trace) = &trace {
+        if let Some(error) = trace.error.lock().unwrap().take() {
+            return Err(error.into());
+        }
+        trace.writer.lock().unwrap().flush()?;
+    }
+
+    for (replication, run) in report.runs.iter().enumerate() {
+        write_stats(&mut output, args.format, replication, run)?;
+        if args.batch_means {
+            write_batch_means(&mut output, args.format, args.confidence, replication, run)?;
+        }
+    }
+    if report.runs.len() > 1 {
+        write_summary(&mut output, args.format, args.confidence, &report)?;
+    }
+    output.flush()?;
+    Ok(())
+}
+
+fn configure(mut network: QueueNetwork, args: &Args, trace: Option<Arc<Trace>>) -> QueueNetwork {
+    if let Some(warm_up) = args.warm_up {
+        network = network.warm_up(warm_up);
+    }
+    if args.real_time {
+        network = network.real_time();
+    }
+    if let Some(speed) = args.speed {
+        network = network.speed(speed);
+    }
+    if args.step_through {
+        network = network.step_through();
+    }
+    if let Some(trace) = trace {
+        let format = args.format;
+        network = network.on_simulation_step(move |network, event| {
+            let mut writer = trace.writer.lock().unwrap();
+            if let Err(error) = write_step(&mut *writer, format, network, event) {
+                trace.error.lock().unwrap().get_or_insert(error);
+            }
         });
+    }
+    network
+}
+
+fn write_step(
+    writer: &mut dyn Write,
+    format: Format,
+    network: &QueueNetwork,
+    Event(time, block_id, event_type, id): Event,
+) -> io::Result<()> {
+    let Some(block) = network.blocks.get(block_id) else {
+        return Ok(());
+    };
+    match format {
+        Format::Text => writeln!(
+            writer,
+            "Elapsed Time: {:.3} | Event: {:?} | Id: {} | {}: {:?}",
+            time.as_secs_f32(),
+            event_type,
+            id,
+            block_id,
+            block.step_stats()
+        ),
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "time": time.as_secs_f64(),
+                "event": event_type,
+                "id": id,
+                "block": block_id,
+                "stats": block.step_stats(),
+            })
+        ),
+        Format::Csv => {
+            for (metric, value) in block.step_stats().metrics() {
+                writeln!(
+                    writer,
+                    "{},{:?},{},{},{},{}",
+                    time.as_secs_f64(),
+                    event_type,
+                    id,
+                    csv_field(block_id),
+                    csv_field(&metric),
+                    value
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
 
-    network.simulate(Duration::from_secs(1000));
+fn write_stats(
+    writer: &mut dyn Write,
+    format: Format,
+    replication: usize,
+    run: &ReplicationRun,
+) -> io::Result<()> {
+    let seed = run.seed;
+    match format {
+        Format::Text => {
+            writeln!(
+                writer,
+                "==== Replication {replication} (seed {seed}) ====\n"
+            )?;
+            for (block_id, stats) in sorted_blocks(&run.stats) {
+                writeln!(writer, "{block_id}: {stats:#?}")?;
+            }
+            writeln!(writer)
+        }
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "replication": replication,
+                "seed": seed,
+                "blocks": run.stats,
+            })
+        ),
+        Format::Csv => {
+            for (block_id, stats) in sorted_blocks(&run.stats) {
+                for (metric, value) in stats.metrics() {
+                    writeln!(
+                        writer,
+                        "{replication},{seed},{},{},{value}",
+                        csv_field(block_id),
+                        csv_field(&metric)
+                    )?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
 
-    println!("\n==== Final Simulation State ====\n");
-    let mut blocks = network.blocks.values().collect::<Vec<_>>();
-    blocks.sort_by_key(|block| match block.id() {
-        "create" => "0".to_string(),
-        "dispose" => "2".to_string(),
-        _ => "1".to_string() + block.id(),
-    });
-    for block in blocks {
-        println!("{}: {:#?}", block.id(), block.stats());
+fn write_batch_means(
+    writer: &mut dyn Write,
+    format: Format,
+    confidence: f64,
+    replication: usize,
+    run: &ReplicationRun,
+) -> io::Result<()> {
+    let seed = run.seed;
+    let blocks = sorted_blocks(&run.stats)
+        .into_iter()
+        .filter_map(|(block_id, _)| Some((block_id, run.batch_means.get(block_id)?)));
+    match format {
+        Format::Text => {
+            writeln!(
+                writer,
+                "==== Batch means of replication {replication} ({}% confidence) ====\n",
+                confidence * 100.0
+            )?;
+            for (block_id, estimates) in blocks {
+                writeln!(writer, "{block_id}:")?;
+                for (metric, batch_means) in estimates {
+                    let estimate = &batch_means.estimate;
+                    writeln!(
+                        writer,
+                        "    {metric}: {:.6} ± {:.6} ({} batches of {:.3}, lag-1 autocorrelation {:.3}{})",
+                        estimate.mean,
+                        estimate.half_width,
+                        estimate.count,
+                        batch_means.batch_size,
+                        batch_means.lag1_autocorrelation,
+                        if batch_means.independent { "" } else { ", not independent" }
+                    )?;
+                }
+            }
+            writeln!(writer)
+        }
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "replication": replication,
+                "seed": seed,
+                "confidence": confidence,
+                "batch_means": run.batch_means,
+            })
+        ),
+        Format::Csv => {
+            for (block_id, estimates) in blocks {
+                for (metric, batch_means) in estimates {
+                    let estimate = &batch_means.estimate;
+                    for (statistic, value) in [
+                        ("mean", estimate.mean),
+                        ("lower", estimate.lower()),
+                        ("upper", estimate.upper()),
+                        ("batches", estimate.count as f64),
+                        ("batch_size", batch_means.batch_size),
+                        ("lag1_autocorrelation", batch_means.lag1_autocorrelation),
+                    ] {
+                        writeln!(
+                            writer,
+                            "{replication},{seed},{},{},{value}",
+                            csv_field(block_id),
+                            csv_field(&format!("batch_means.{metric}.{statistic}"))
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        }
     }
 }
+
+fn write_summary(
+    writer: &mut dyn Write,
+    format: Format,
+    confidence: f64,
+    report: &ReplicationReport,
+) -> io::Result<()> {
+    let runs = report.runs.len();
+    let last_run = &report.runs[runs - 1].stats;
+    match format {
+        Format::Text => {
+            writeln!(
+                writer,
+                "==== Summary of {runs} replications ({}% confidence) ====\n",
+                confidence * 100.0
+            )?;
+            for (block_id, _) in sorted_blocks(last_run) {
+                writeln!(writer, "{block_id}:")?;
+                for (metric, estimate) in &report.summary[block_id] {
+                    writeln!(
+                        writer,
+                        "    {metric}: {:.6} ± {:.6} (std dev {:.6})",
+                        estimate.mean, estimate.half_width, estimate.std_dev
+                    )?;
+                }
+            }
+            writeln!(writer)
+        }
+        Format::Json => writeln!(
+            writer,
+            "{}",
+            json!({
+                "replications": runs,
+                "confidence": confidence,
+                "summary": report.summary,
+            })
+        ),
+        Format::Csv => {
+            for (block_id, _) in sorted_blocks(last_run) {
+                for (metric, estimate) in &report.summary[block_id] {
+                    for (statistic, value) in [
+                        ("mean", estimate.mean),
+                        ("std_dev", estimate.std_dev),
+                        ("lower", estimate.lower()),
+                        ("upper", estimate.upper()),
+                    ] {
+                        writeln!(
+                            writer,
+                            "{statistic},,{},{},{value}",
+                            csv_field(block_id),
+                            csv_field(metric)
+                        )?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Quotes `value` if it contains characters with a special meaning in CSV.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Blocks in the order entities flow through them: create, process, dispose
+/// and other blocks, each sorted by id.
+fn sorted_blocks(stats: &BTreeMap<BlockId, BlockStats>) -> Vec<(BlockId, &BlockStats)> {
+    let mut blocks = stats
+        .iter()
+        .map(|(&block_id, stats)| (block_id, stats))
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|&(block_id, stats)| {
+        let rank = match stats {
+            BlockStats::Create(_) => 0,
+            BlockStats::Process(_) => 1,
+            BlockStats::Dispose(_) => 2,
+            BlockStats::Other(_) => 3,
+        };
+        (rank, block_id)
+    });
+    blocks
+}
+
+fn create_writer(path: Option<&PathBuf>) -> io::Result<Box<dyn Write + Send>> {
+    Ok(match path {
+        Some(path) if path.as_os_str() != "-" => Box::new(BufWriter::new(File::create(path)?)),
+        _ => Box::new(BufWriter::new(io::stdout())),
+    })
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let seconds = value.parse::<f64>().map_err(|error| error.to_string())?;
+    Duration::try_from_secs_f64(seconds).map_err(|error| error.to_string())
+}
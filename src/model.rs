@@ -0,0 +1,717 @@
+//! Networks described declaratively in TOML or JSON files.
+
+use crate::{
+    blocks::{BlockId, Blocking, CreateBlock, DisposeBlock, Preemption, ProcessBlock},
+    devices::Devices,
+    disciplines::{
+        EarliestDeadlineFirst, Fifo, Lifo, QueueDiscipline, RandomOrder, ShortestJobFirst,
+    },
+    distributions::{AnyDistribution, Deterministic},
+    entity::Value,
+    network::QueueNetwork,
+    queue::Queue,
+    routers::{DirectRouter, ProbabilityRouter, Router},
+    samples::Samples,
+};
+use rand_distr::{Exp, Gamma, LogNormal, Normal, Triangular, Uniform};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+/// Declarative description of a network, loaded from a TOML or JSON file.
+///
+/// ```toml
+/// seed = 42
+///
+/// [[blocks]]
+/// type = "create"
+/// id = "create"
+/// distribution = { type = "exponential", rate = 0.5 }
+/// router = { type = "direct", next = "process" }
+/// kind = "regular"
+/// attributes = { vip = false }
+///
+/// [[blocks]]
+/// type = "process"
+/// id = "process"
+/// distribution = { type = "exponential", rate = 1.0 }
+/// queue = { capacity = 5, discipline = { type = "shortest_job_first" } }
+/// devices = 2
+/// router = { type = "probability", next = [[0.9, "dispose"], [0.1, "process"]] }
+///
+/// [[blocks]]
+/// type = "dispose"
+/// id = "dispose"
+/// system_times = { type = "p2", percentiles = [0.5, 0.95, 0.99] }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Model {
+    /// Seed of the network, drawn at random when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Every block of the network, added in order.
+    pub blocks: Vec<BlockModel>,
+    /// Groups of process blocks whose waiting entities switch queues.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jockeying: Vec<JockeyingModel>,
+}
+
+/// Process blocks whose waiting entities switch to shorter queues, see
+/// [`QueueNetwork::jockeying`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JockeyingModel {
+    /// Ids of the blocks of the group.
+    pub blocks: Vec<String>,
+    /// Length difference that must be exceeded before an entity switches.
+    #[serde(default = "default_threshold")]
+    pub threshold: usize,
+}
+
+/// A block of a [`Model`], tagged by its `type`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BlockModel {
+    /// A [`CreateBlock`].
+    Create {
+        /// Name of the block, unique within the model.
+        id: String,
+        /// Distribution of the time between created entities, in seconds.
+        distribution: DistributionModel,
+        /// Choice of the block every created entity enters.
+        router: RouterModel,
+        /// Time of the first created entity, in seconds.
+        #[serde(default)]
+        first_at: f32,
+        /// Kind of created entities.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<String>,
+        /// Priority class of created entities.
+        #[serde(default)]
+        priority: i32,
+        /// Initial attributes of created entities.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        attributes: BTreeMap<String, Value>,
+    },
+    /// A [`ProcessBlock`].
+    Process {
+        /// Name of the block, unique within the model.
+        id: String,
+        /// Distribution of service times, in seconds.
+        distribution: DistributionModel,
+        /// Choice of the block every served entity moves to.
+        router: RouterModel,
+        /// Without a queue, arrivals finding every device busy are rejected.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        queue: Option<Box<QueueModel>>,
+        /// Number of devices, one when missing.
+        #[serde(default = "default_devices")]
+        devices: usize,
+        /// Whether higher priority arrivals interrupt services, never when
+        /// missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preemption: Option<Preemption>,
+        /// Time queued entities wait before abandoning the queue, forever
+        /// when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        patience: Option<DistributionModel>,
+        /// Block receiving entities that abandon the queue. Without one they
+        /// leave the network.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        abandon_to: Option<String>,
+        /// Probability of joining by queue length found on arrival, the last
+        /// one for longer queues. Arrivals always join when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        balking: Option<Vec<f64>>,
+        /// Block receiving arrivals that balk. Without one they leave the
+        /// network.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        balk_to: Option<String>,
+        /// What happens when the next block is full as a service ends,
+        /// rejection when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blocking: Option<Blocking>,
+        /// Summary of waits before service, P² when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wait_times: Option<SamplesModel>,
+        /// Summary of service times, P² when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        service_times: Option<SamplesModel>,
+    },
+    /// A [`DisposeBlock`].
+    Dispose {
+        /// Name of the block, unique within the model.
+        id: String,
+        /// Summary of times in system, P² when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        system_times: Option<SamplesModel>,
+    },
+}
+
+/// The [`Queue`] of a process block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueModel {
+    /// Unbounded when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<usize>,
+    /// Summary of waits in the queue, P² when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waits: Option<SamplesModel>,
+    /// Reported percentiles of the time-weighted length.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length_percentiles: Option<Vec<f64>>,
+    /// Serve higher entity priorities first, first come first served within
+    /// a priority.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub priority: bool,
+    /// Order of service, FIFO when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discipline: Option<DisciplineModel>,
+}
+
+/// Order in which a queue serves waiting entities, see [`QueueDiscipline`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DisciplineModel {
+    /// First in, first out.
+    Fifo,
+    /// Last in, first out.
+    Lifo,
+    /// Service in random order.
+    Random,
+    /// Shortest service time first, sampled on arrival.
+    ShortestJobFirst,
+    /// Earliest deadline first.
+    EarliestDeadlineFirst {
+        /// Numeric entity attribute holding the deadline, in seconds.
+        attribute: String,
+    },
+}
+
+/// Estimator summarizing a per-entity metric, see [`Samples`]. Percentiles
+/// are given as probabilities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SamplesModel {
+    /// Every observation kept, see [`Samples::exact`].
+    Exact {
+        /// Reported percentiles, the defaults when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentiles: Option<Vec<f64>>,
+    },
+    /// Fixed-width histogram, see [`Samples::histogram`].
+    Histogram {
+        /// Lower bound of the first bucket.
+        min: f64,
+        /// Width of every bucket.
+        width: f64,
+        /// Number of buckets.
+        buckets: usize,
+        /// Reported percentiles, the defaults when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentiles: Option<Vec<f64>>,
+    },
+    /// P² estimates, see [`Samples::p2`].
+    P2 {
+        /// Reported percentiles, the defaults when missing.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        percentiles: Option<Vec<f64>>,
+    },
+}
+
+/// Distribution of a time in seconds, see [`AnyDistribution`]. Parameters
+/// out of range fail [`Model::build`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DistributionModel {
+    /// Always `value`.
+    Deterministic {
+        /// The value.
+        value: f32,
+    },
+    /// Exponential distribution.
+    Exponential {
+        /// Rate, the inverse of the mean.
+        rate: f32,
+    },
+    /// Uniform distribution over `[min, max)`.
+    Uniform {
+        /// Lower bound.
+        min: f32,
+        /// Upper bound.
+        max: f32,
+    },
+    /// Normal distribution.
+    Normal {
+        /// Mean.
+        mean: f32,
+        /// Standard deviation.
+        std_dev: f32,
+    },
+    /// Log-normal distribution.
+    LogNormal {
+        /// Mean of the logarithm.
+        mu: f32,
+        /// Standard deviation of the logarithm.
+        sigma: f32,
+    },
+    /// Gamma distribution.
+    Gamma {
+        /// Shape.
+        shape: f32,
+        /// Scale.
+        scale: f32,
+    },
+    /// Erlang distribution, the sum of `shape` exponential phases.
+    Erlang {
+        /// Number of phases.
+        shape: u32,
+        /// Rate of every phase.
+        rate: f32,
+    },
+    /// Triangular distribution.
+    Triangular {
+        /// Lower bound.
+        min: f32,
+        /// Most likely value.
+        mode: f32,
+        /// Upper bound.
+        max: f32,
+    },
+}
+
+/// Router of a block, see [`Router`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum RouterModel {
+    /// Always to the same block, see [`DirectRouter`].
+    Direct {
+        /// Id of the block.
+        next: String,
+    },
+    /// To one of several blocks at random, see [`ProbabilityRouter`].
+    Probability {
+        /// Weight and id of every block.
+        next: Vec<(f32, String)>,
+    },
+}
+
+/// Error loading a [`Model`] or building a network from it.
+#[derive(Debug)]
+pub enum ModelError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The TOML source is not a valid model.
+    Toml(toml::de::Error),
+    /// The JSON source is not a valid model.
+    Json(serde_json::Error),
+    /// The file extension is neither `toml` nor `json`.
+    UnknownFormat(PathBuf),
+    /// Parameters of the distribution of `block` are out of range.
+    InvalidDistribution {
+        /// Id of the block.
+        block: String,
+        /// Why the parameters are out of range.
+        message: String,
+    },
+    /// Another parameter of `block` is out of range.
+    InvalidParameter {
+        /// Id of the block.
+        block: String,
+        /// Which parameter is out of range, and why.
+        message: String,
+    },
+}
+
+impl Model {
+    /// Parses a model from TOML.
+    pub fn from_toml(source: &str) -> Result<Self, ModelError> {
+        toml::from_str(source).map_err(ModelError::Toml)
+    }
+
+    /// Parses a model from JSON.
+    pub fn from_json(source: &str) -> Result<Self, ModelError> {
+        serde_json::from_str(source).map_err(ModelError::Json)
+    }
+
+    /// Loads a model, choosing the format by the `toml` or `json` extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(ModelError::Io)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&source),
+            Some("json") => Self::from_json(&source),
+            _ => Err(ModelError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Builds a network with the blocks of the model, in order.
+    ///
+    /// Block ids are interned for the lifetime of the process, so building the
+    /// same model repeatedly does not allocate new ids.
+    pub fn build(&self) -> Result<QueueNetwork, ModelError> {
+        let mut network = QueueNetwork::new();
+        if let Some(seed) = self.seed {
+            network = network.seed(seed);
+        }
+        for block in &self.blocks {
+            network = match block {
+                BlockModel::Create {
+                    id,
+                    distribution,
+                    router,
+                    first_at,
+                    kind,
+                    priority,
+                    attributes,
+                } => {
+                    let first_at = Duration::try_from_secs_f32(*first_at).map_err(|error| {
+                        ModelError::InvalidParameter {
+                            block: id.clone(),
+                            message: format!("first_at: {error}"),
+                        }
+                    })?;
+                    let mut builder = CreateBlock::builder(intern(id))
+                        .distribution(distribution.build(id)?)
+                        .router(router.build())
+                        .first_at(first_at)
+                        .priority(*priority);
+                    if let Some(kind) = kind {
+                        builder = builder.kind(kind);
+                    }
+                    for (key, value) in attributes {
+                        builder = builder.attribute(key, value.clone());
+                    }
+                    network.add_block(builder.build())
+                }
+                BlockModel::Process {
+                    id,
+                    distribution,
+                    router,
+                    queue,
+                    devices,
+                    preemption,
+                    patience,
+                    abandon_to,
+                    balking,
+                    balk_to,
+                    blocking,
+                    wait_times,
+                    service_times,
+                } => {
+                    if *devices == 0 {
+                        return Err(ModelError::InvalidParameter {
+                            block: id.clone(),
+                            message: "devices must be at least 1".to_string(),
+                        });
+                    }
+                    let mut builder = ProcessBlock::builder(intern(id))
+                        .distribution(distribution.build(id)?)
+                        .router(router.build())
+                        .devices(Devices::new(*devices));
+                    if let Some(queue) = queue {
+                        builder = builder.queue(queue.build());
+                    }
+                    if let Some(preemption) = preemption {
+                        builder = builder.preemption(*preemption);
+                    }
+                    if let Some(patience) = patience {
+                        builder = builder.patience(patience.build(id)?);
+                    }
+                    if let Some(abandon_to) = abandon_to {
+                        builder = builder.abandon_to(intern(abandon_to));
+                    }
+                    if let Some(balking) = balking {
+                        builder = builder.balking_table(balking);
+                    }
+                    if let Some(balk_to) = balk_to {
+                        builder = builder.balk_to(intern(balk_to));
+                    }
+                    if let Some(blocking) = blocking {
+                        builder = builder.blocking(*blocking);
+                    }
+                    if let Some(wait_times) = wait_times {
+                        builder = builder.wait_times(wait_times.build());
+                    }
+                    if let Some(service_times) = service_times {
+                        builder = builder.service_times(service_times.build());
+                    }
+                    network.add_block(builder.build())
+                }
+                BlockModel::Dispose { id, system_times } => {
+                    let mut block = DisposeBlock::new(intern(id));
+                    if let Some(system_times) = system_times {
+                        block = block.system_times(system_times.build());
+                    }
+                    network.add_block(block)
+                }
+            };
+        }
+        for group in &self.jockeying {
+            let blocks = group
+                .blocks
+                .iter()
+                .map(|block_id| intern(block_id))
+                .collect::<Vec<_>>();
+            network = network.jockeying(&blocks, group.threshold);
+        }
+        Ok(network)
+    }
+}
+
+impl QueueModel {
+    fn build(&self) -> Queue {
+        let mut queue = match self.capacity {
+            Some(capacity) => Queue::from_capacity(capacity),
+            None => Queue::default(),
+        };
+        if let Some(waits) = &self.waits {
+            queue = queue.waits(waits.build());
+        }
+        if let Some(percentiles) = &self.length_percentiles {
+            queue = queue.length_percentiles(percentiles);
+        }
+        if self.priority {
+            queue = queue.priority();
+        }
+        if let Some(discipline) = &self.discipline {
+            queue = queue.discipline(discipline.build());
+        }
+        queue
+    }
+}
+
+impl DisciplineModel {
+    fn build(&self) -> Box<dyn QueueDiscipline> {
+        match self {
+            Self::Fifo => Box::new(Fifo),
+            Self::Lifo => Box::new(Lifo),
+            Self::Random => Box::new(RandomOrder::new()),
+            Self::ShortestJobFirst => Box::new(ShortestJobFirst),
+            Self::EarliestDeadlineFirst { attribute } => {
+                Box::new(EarliestDeadlineFirst::new(attribute.as_str()))
+            }
+        }
+    }
+}
+
+impl SamplesModel {
+    fn build(&self) -> Samples {
+        let (samples, percentiles) = match self {
+            Self::Exact { percentiles } => (Samples::exact(), percentiles),
+            Self::Histogram {
+                min,
+                width,
+                buckets,
+                percentiles,
+            } => (Samples::histogram(*min, *width, *buckets), percentiles),
+            Self::P2 { percentiles } => (Samples::p2(), percentiles),
+        };
+        match percentiles {
+            Some(percentiles) => samples.percentiles(percentiles),
+            None => samples,
+        }
+    }
+}
+
+impl DistributionModel {
+    fn build(&self, block: &str) -> Result<AnyDistribution, ModelError> {
+        let invalid = |error: &dyn fmt::Display| ModelError::InvalidDistribution {
+            block: block.to_string(),
+            message: error.to_string(),
+        };
+        Ok(match *self {
+            Self::Deterministic { value } => {
+                AnyDistribution::Deterministic(Deterministic::new(value))
+            }
+            Self::Exponential { rate } => {
+                AnyDistribution::Exponential(Exp::new(rate).map_err(|e| invalid(&e))?)
+            }
+            Self::Uniform { min, max } => {
+                AnyDistribution::Uniform(Uniform::new(min, max).map_err(|e| invalid(&e))?)
+            }
+            Self::Normal { mean, std_dev } => {
+                AnyDistribution::Normal(Normal::new(mean, std_dev).map_err(|e| invalid(&e))?)
+            }
+            Self::LogNormal { mu, sigma } => {
+                AnyDistribution::LogNormal(LogNormal::new(mu, sigma).map_err(|e| invalid(&e))?)
+            }
+            Self::Gamma { shape, scale } => {
+                AnyDistribution::Gamma(Gamma::new(shape, scale).map_err(|e| invalid(&e))?)
+            }
+            Self::Erlang { rate, .. } if rate <= 0.0 => {
+                return Err(invalid(&format!("rate {rate} is not positive")));
+            }
+            Self::Erlang { shape, rate } => AnyDistribution::Gamma(
+                Gamma::new(shape as f32, 1.0 / rate).map_err(|e| invalid(&e))?,
+            ),
+            Self::Triangular { min, mode, max } => AnyDistribution::Triangular(
+                Triangular::new(min, max, mode).map_err(|e| invalid(&e))?,
+            ),
+        })
+    }
+}
+
+impl RouterModel {
+    fn build(&self) -> Box<dyn Router> {
+        match self {
+            Self::Direct { next } => Box::new(DirectRouter::new(intern(next))),
+            Self::Probability { next } => Box::new(ProbabilityRouter::new(
+                &next
+                    .iter()
+                    .map(|(weight, block_id)| (*weight, intern(block_id)))
+                    .collect::<Vec<_>>(),
+            )),
+        }
+    }
+}
+
+fn default_devices() -> usize {
+    1
+}
+
+fn default_threshold() -> usize {
+    1
+}
+
+fn intern(id: &str) -> BlockId {
+    static IDS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut ids = IDS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&interned) = ids.get(id) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(id.into());
+    ids.insert(interned);
+    interned
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read model: {error}"),
+            Self::Toml(error) => write!(f, "invalid TOML model: {error}"),
+            Self::Json(error) => write!(f, "invalid JSON model: {error}"),
+            Self::UnknownFormat(path) => write!(
+                f,
+                "unknown model format of `{}`, expected a .toml or .json file",
+                path.display()
+            ),
+            Self::InvalidDistribution { block, message } => {
+                write!(f, "invalid distribution of block `{block}`: {message}")
+            }
+            Self::InvalidParameter { block, message } => {
+                write!(f, "invalid parameter of block `{block}`: {message}")
+            }
+        }
+    }
+}
+
+impl Error for ModelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Toml(error) => Some(error),
+            Self::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The TOML example in the documentation of [`Model`].
+    fn documented_example() -> String {
+        include_str!("model.rs")
+            .lines()
+            .skip_while(|line| *line != "/// ```toml")
+            .skip(1)
+            .take_while(|line| *line != "/// ```")
+            .map(|line| line.trim_start_matches("///").trim_start())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn assert_round_trip(model: Model) {
+        model.build().unwrap().validate().unwrap();
+        let source = toml::to_string(&model).unwrap();
+        let reparsed = Model::from_toml(&source).unwrap();
+        assert_eq!(toml::to_string(&reparsed).unwrap(), source);
+        reparsed.build().unwrap();
+    }
+
+    fn create(first_at: f32, distribution: &str) -> Model {
+        Model::from_toml(&format!(
+            r#"
+            [[blocks]]
+            type = "create"
+            id = "create"
+            first_at = {first_at}
+            distribution = {distribution}
+            router = {{ type = "direct", next = "dispose" }}
+
+            [[blocks]]
+            type = "dispose"
+            id = "dispose"
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn documented_example_round_trips() {
+        let model = Model::from_toml(&documented_example()).unwrap();
+        assert_eq!(model.seed, Some(42));
+        assert_eq!(model.blocks.len(), 3);
+        assert_round_trip(model);
+    }
+
+    #[test]
+    fn example_file_round_trips() {
+        let model =
+            Model::load(concat!(env!("CARGO_MANIFEST_DIR"), "/models/example.toml")).unwrap();
+        assert_eq!(model.blocks.len(), 5);
+        assert_round_trip(model);
+    }
+
+    #[test]
+    fn negative_first_arrival_is_rejected() {
+        let model = create(-1.0, r#"{ type = "deterministic", value = 1.0 }"#);
+        assert!(matches!(
+            model.build(),
+            Err(ModelError::InvalidParameter { block, .. }) if block == "create"
+        ));
+        assert!(create(0.0, r#"{ type = "deterministic", value = 1.0 }"#)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn erlang_needs_a_positive_rate() {
+        for rate in ["0.0", "-1.0", "nan"] {
+            let model = create(
+                0.0,
+                &format!(r#"{{ type = "erlang", shape = 2, rate = {rate} }}"#),
+            );
+            assert!(matches!(
+                model.build(),
+                Err(ModelError::InvalidDistribution { block, .. }) if block == "create"
+            ));
+        }
+        assert!(create(0.0, r#"{ type = "erlang", shape = 2, rate = 0.5 }"#)
+            .build()
+            .is_ok());
+    }
+}
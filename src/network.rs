@@ -1,96 +1,883 @@
+//! The simulated network of blocks and its event loop.
+
 use crate::{
-    blocks::{Block, BlockId},
-    events::{Event, EventType},
+    batch_means::BatchMeans,
+    blocks::{Block, BlockId, BlockKind, BlockStats, Blocking},
+    entity::Entities,
+    error::{SimulationError, ValidationError},
+    events::{Event, EventQueue, EventType},
+    random::stream_seed,
 };
+use rand::{rng, Rng};
 use std::{
-    collections::{BinaryHeap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     io::stdin,
     thread,
     time::Duration,
 };
 
+/// Callback invoked after every processed event.
+pub type OnSimulationStep = Box<dyn Fn(&QueueNetwork, Event) + Send>;
+
+/// A set of connected blocks together with the future event list driving them.
+///
+/// Networks are `Send`, so that independent runs can be simulated on separate
+/// threads.
 pub struct QueueNetwork {
-    event_queue: BinaryHeap<Event>,
+    event_queue: EventQueue,
     speed: Option<f32>,
     step_through: bool,
-    on_simulation_step: Box<dyn Fn(&QueueNetwork, Event)>,
+    on_simulation_step: OnSimulationStep,
+    /// Seed of the run. Drawn at random by [`QueueNetwork::simulate`] when not
+    /// set, so that any run can be replayed.
+    pub seed: Option<u64>,
+    warm_up: Option<Duration>,
+    /// Simulation time reached so far, i.e. the end of the observation period
+    /// once [`QueueNetwork::simulate`] returns.
+    pub time: Duration,
+    /// Every block of the network, keyed by id.
     pub blocks: HashMap<BlockId, Box<dyn Block>>,
+    /// Every entity created and not yet disposed of.
+    pub entities: Entities,
+    duplicate_blocks: Vec<BlockId>,
+    jockeying: Vec<JockeyingGroup>,
+    /// Entities whose service ended while their next block was full, in the
+    /// order they got blocked.
+    blocked: Vec<BlockedEntity>,
+}
+
+/// An entity kept on a device of `block` until `next` has room.
+struct BlockedEntity {
+    block: BlockId,
+    entity_id: usize,
+    next: BlockId,
+}
+
+/// Blocks whose waiting entities switch to the shortest queue of the group
+/// when it is more than `threshold` shorter than theirs.
+struct JockeyingGroup {
+    blocks: Vec<BlockId>,
+    threshold: usize,
+}
+
+impl Default for QueueNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl QueueNetwork {
+    /// Creates an empty network, unseeded and unpaced. Blocks are added with
+    /// [`QueueNetwork::add_block`].
     pub fn new() -> QueueNetwork {
         QueueNetwork {
-            event_queue: BinaryHeap::new(),
+            event_queue: EventQueue::new(),
             speed: None,
             step_through: false,
             on_simulation_step: Box::new(|_, _| {}),
+            seed: None,
+            warm_up: None,
+            time: Duration::ZERO,
             blocks: HashMap::new(),
+            entities: Entities::new(),
+            duplicate_blocks: Vec::new(),
+            jockeying: Vec::new(),
+            blocked: Vec::new(),
         }
     }
 
+    /// Paces the simulation so that one simulated second takes one real second.
     pub fn real_time(mut self) -> Self {
         self.speed = Some(1.0);
         self
     }
 
+    /// Paces the simulation at `speed` simulated seconds per real second.
     pub fn speed(mut self, speed: f32) -> Self {
         self.speed = Some(speed);
         self
     }
 
+    /// Waits for a line on stdin before processing each event.
     pub fn step_through(mut self) -> Self {
         self.step_through = true;
         self
     }
 
+    /// Seeds the network. Every block and router draws from its own stream,
+    /// derived from this seed and the block id.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Discards the statistics of every block once the simulation reaches
+    /// `warm_up`, so that they only describe the system after its transient
+    /// phase. The warm-up must end before the simulation does.
+    pub fn warm_up(mut self, warm_up: Duration) -> Self {
+        self.warm_up = Some(warm_up);
+        self
+    }
+
+    /// Orders simultaneous events by `priority`, highest first, for example by
+    /// event type or by block. Events with equal time and priority run in the
+    /// order they were scheduled.
+    pub fn event_priority(mut self, priority: impl Fn(&Event) -> i32 + Send + 'static) -> Self {
+        self.event_queue = EventQueue::with_priority(priority);
+        self
+    }
+
+    /// Adds a block, keyed by its [`Block::id`]. A block with the same id
+    /// replaces the previous one and is reported by
+    /// [`QueueNetwork::validate`].
     pub fn add_block(mut self, block: impl Block + 'static) -> Self {
-        self.blocks.insert(block.id(), Box::new(block));
+        if let Some(previous) = self.blocks.insert(block.id(), Box::new(block)) {
+            self.duplicate_blocks.push(previous.id());
+        }
+        self
+    }
+
+    /// Links the queues of `blocks`, e.g. parallel checkout lanes. After every
+    /// event, while the longest queue of the group is more than `threshold`
+    /// entities longer than the shortest one with room, the entity the longest
+    /// queue would serve last moves to that one, keeping its time of arrival
+    /// and patience. A threshold of zero behaves as one, since moving
+    /// an entity between queues differing by one would only swap their
+    /// lengths. Ties go to the block listed first.
+    pub fn jockeying(mut self, blocks: &[BlockId], threshold: usize) -> Self {
+        self.jockeying.push(JockeyingGroup {
+            blocks: blocks.to_vec(),
+            threshold: threshold.max(1),
+        });
         self
     }
 
+    /// Sets the callback invoked after every processed event.
     pub fn on_simulation_step(
         mut self,
-        on_simulation_step: impl Fn(&QueueNetwork, Event) + 'static,
+        on_simulation_step: impl Fn(&QueueNetwork, Event) + Send + 'static,
     ) -> Self {
         self.on_simulation_step = Box::new(on_simulation_step);
         self
     }
 
-    pub fn simulate(&mut self, duration: Duration) {
+    /// Statistics of every block, keyed by block id.
+    pub fn stats(&self) -> BTreeMap<BlockId, BlockStats> {
+        self.blocks
+            .iter()
+            .map(|(&block_id, block)| (block_id, block.stats()))
+            .collect()
+    }
+
+    /// Steady-state estimates of every block's metrics by the method of batch
+    /// means, from the single run simulated so far.
+    pub fn batch_means(&self, confidence: f64) -> BTreeMap<BlockId, Vec<(String, BatchMeans)>> {
+        self.blocks
+            .iter()
+            .map(|(&block_id, block)| (block_id, block.batch_means(self.time, confidence)))
+            .filter(|(_, estimates)| !estimates.is_empty())
+            .collect()
+    }
+
+    /// Discards the statistics of every block collected until
+    /// `simulation_duration`.
+    pub fn reset_stats(&mut self, simulation_duration: Duration) {
         for block in self.blocks.values_mut() {
-            block.init(&mut self.event_queue);
+            block.reset_stats(simulation_duration);
+        }
+    }
+
+    /// Checks the topology of the network: duplicate block ids, routes to
+    /// unknown blocks, invalid router weights, blocks unreachable from any
+    /// create block, process blocks that can never reach a dispose block and
+    /// jockeying groups with blocks lacking a queue. Members of a jockeying
+    /// group count as reachable from each other.
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = self
+            .duplicate_blocks
+            .iter()
+            .map(|&block_id| ValidationError::DuplicateBlock(block_id))
+            .collect::<Vec<_>>();
+
+        let mut block_ids = self.blocks.keys().copied().collect::<Vec<_>>();
+        block_ids.sort();
+        let mut sources = HashMap::<BlockId, Vec<BlockId>>::new();
+        for &block_id in &block_ids {
+            let block = &self.blocks[block_id];
+            block.validate(&mut errors);
+            for target in block.targets() {
+                if self.blocks.contains_key(target) {
+                    sources.entry(target).or_default().push(block_id);
+                } else {
+                    errors.push(ValidationError::DanglingTarget {
+                        block: block_id,
+                        target,
+                    });
+                }
+            }
         }
 
+        let reachable = self.search(BlockKind::Create, |block_id| {
+            let mut targets = self.blocks[block_id].targets();
+            targets.extend(self.jockeying_peers(block_id));
+            targets
+        });
+        let reaching_dispose = self.search(BlockKind::Dispose, |block_id| {
+            let mut sources = sources.get(block_id).cloned().unwrap_or_default();
+            sources.extend(self.jockeying_peers(block_id));
+            sources
+        });
+        for &block_id in &block_ids {
+            let kind = self.blocks[block_id].kind();
+            if kind != BlockKind::Create && !reachable.contains(block_id) {
+                errors.push(ValidationError::Unreachable(block_id));
+            }
+            if kind == BlockKind::Process && !reaching_dispose.contains(block_id) {
+                errors.push(ValidationError::NoPathToDispose(block_id));
+            }
+        }
+
+        for group in &self.jockeying {
+            for &block_id in &group.blocks {
+                if self
+                    .blocks
+                    .get(block_id)
+                    .and_then(|block| block.queue_len())
+                    .is_none()
+                {
+                    errors.push(ValidationError::NotJockeyable(block_id));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Hands `entity_id` to `block` at once rather than through an `In` event,
+    /// so that the room the entity was checked against cannot be taken by
+    /// another arrival first. The step callback still sees it as an `In`
+    /// event.
+    fn deliver(
+        &mut self,
+        block_id: BlockId,
+        entity_id: usize,
+        time: Duration,
+    ) -> Result<(), SimulationError> {
+        let mut block = self
+            .blocks
+            .remove(block_id)
+            .ok_or(SimulationError::UnknownBlock(block_id))?;
+        let result = block.process_in(entity_id, &mut self.entities, &mut self.event_queue, time);
+        self.blocks.insert(block_id, block);
+        result?;
+        (self.on_simulation_step)(self, Event(time, block_id, EventType::In, entity_id));
+        Ok(())
+    }
+
+    /// Lets blocked entities go, first blocked first, as their next blocks get
+    /// room, then tells blocks in `before_service` whether they may start
+    /// services. Every released entity frees room in its own block, so the
+    /// blocked entities are checked again after each release.
+    fn unblock(
+        &mut self,
+        time: Duration,
+        before_service: &[BlockId],
+    ) -> Result<(), SimulationError> {
+        while let Some(index) = self.blocked.iter().position(|blocked| {
+            self.blocks
+                .get(blocked.next)
+                .is_none_or(|next| next.accepts())
+        }) {
+            let BlockedEntity {
+                block: block_id,
+                entity_id,
+                next,
+            } = self.blocked.remove(index);
+            let block = self
+                .blocks
+                .get_mut(block_id)
+                .ok_or(SimulationError::UnknownBlock(block_id))?;
+            block.unblock(entity_id, time);
+            block.process_out(entity_id, &mut self.entities, &mut self.event_queue, time)?;
+            self.deliver(next, entity_id, time)?;
+        }
+        for &block_id in before_service {
+            let ready = self.blocks[block_id].route_targets().iter().all(|&target| {
+                target == block_id
+                    || self
+                        .blocks
+                        .get(target)
+                        .is_none_or(|target| target.accepts())
+            });
+            self.blocks
+                .get_mut(block_id)
+                .ok_or(SimulationError::UnknownBlock(block_id))?
+                .set_downstream_ready(ready, &mut self.event_queue, time)?;
+        }
+        Ok(())
+    }
+
+    /// Moves entities from longer to shorter queues within every jockeying
+    /// group, see [`QueueNetwork::jockeying`].
+    fn jockey(&mut self, time: Duration) -> Result<(), SimulationError> {
+        let Self {
+            jockeying,
+            blocks,
+            entities,
+            event_queue,
+            ..
+        } = self;
+        for group in jockeying.iter() {
+            loop {
+                let lengths = group
+                    .blocks
+                    .iter()
+                    .filter_map(|&block_id| {
+                        let block = blocks.get(block_id)?;
+                        Some((block_id, block.queue_len()?, block.accepts()))
+                    })
+                    .collect::<Vec<_>>();
+                let longest = lengths
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, length, _)| *length)
+                    .map(|&(block_id, length, _)| (block_id, length));
+                let shortest = lengths
+                    .iter()
+                    .filter(|(_, _, accepts)| *accepts)
+                    .min_by_key(|(_, length, _)| *length)
+                    .map(|&(block_id, length, _)| (block_id, length));
+                let (Some((from, longest)), Some((to, shortest))) = (longest, shortest) else {
+                    break;
+                };
+                if longest - shortest <= group.threshold {
+                    break;
+                }
+                let Some(jockey) = blocks
+                    .get_mut(from)
+                    .and_then(|block| block.jockey_out(event_queue, time))
+                else {
+                    break;
+                };
+                blocks
+                    .get_mut(to)
+                    .ok_or(SimulationError::UnknownBlock(to))?
+                    .jockey_in(jockey, entities, event_queue, time)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocks sharing a jockeying group with `block_id`, which entities can
+    /// move to from its queue and back.
+    fn jockeying_peers(&self, block_id: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+        self.jockeying
+            .iter()
+            .filter(move |group| group.blocks.contains(&block_id))
+            .flat_map(|group| group.blocks.iter().copied())
+            .filter(move |&peer| peer != block_id)
+    }
+
+    /// Every block reachable from blocks of kind `from` through `neighbours`.
+    fn search(
+        &self,
+        from: BlockKind,
+        neighbours: impl Fn(BlockId) -> Vec<BlockId>,
+    ) -> HashSet<BlockId> {
+        let mut stack = self
+            .blocks
+            .values()
+            .filter(|block| block.kind() == from)
+            .map(|block| block.id())
+            .collect::<Vec<_>>();
+        let mut visited = stack.iter().copied().collect::<HashSet<_>>();
+        while let Some(block_id) = stack.pop() {
+            for neighbour in neighbours(block_id) {
+                if self.blocks.contains_key(neighbour) && visited.insert(neighbour) {
+                    stack.push(neighbour);
+                }
+            }
+        }
+        visited
+    }
+
+    /// Runs the simulation until the next event would happen at or after
+    /// `duration`. Fails without simulating anything if the network does not
+    /// pass [`QueueNetwork::validate`] or the warm-up would never end.
+    pub fn simulate(&mut self, duration: Duration) -> Result<(), SimulationError> {
+        self.validate().map_err(SimulationError::Invalid)?;
+        if let Some(warm_up) = self.warm_up.filter(|&warm_up| warm_up >= duration) {
+            return Err(SimulationError::WarmUpTooLong { warm_up, duration });
+        }
+        let seed = *self.seed.get_or_insert_with(|| rng().random());
+        let mut block_ids = self.blocks.keys().copied().collect::<Vec<_>>();
+        block_ids.sort();
+        for block_id in block_ids {
+            let block = self
+                .blocks
+                .get_mut(block_id)
+                .expect("block id was just listed");
+            block.seed(stream_seed(seed, block_id));
+            block.init(&mut self.entities, &mut self.event_queue)?;
+        }
+
+        let mut before_service = self
+            .blocks
+            .values()
+            .filter(|block| block.blocking() == Blocking::BeforeService)
+            .map(|block| block.id())
+            .collect::<Vec<_>>();
+        before_service.sort();
+        self.unblock(Duration::ZERO, &before_service)?;
+
+        let mut warm_up = self.warm_up;
         let mut prev_time = Duration::from_secs(0);
         while let Some(Event(time, block_id, event_type, id)) = self.event_queue.pop() {
             if self.step_through {
-                stdin().read_line(&mut String::new()).unwrap();
+                stdin().read_line(&mut String::new())?;
             } else if let Some(speed) = self.speed {
                 thread::sleep(Duration::from_secs_f32(
                     (time - prev_time).as_secs_f32() / speed,
                 ));
                 prev_time = time
             }
+            if let Some(end) = warm_up.filter(|&end| time >= end) {
+                self.reset_stats(end);
+                warm_up = None;
+            }
             if time >= duration {
                 break;
             }
-            let expect_message = "event queue should only contain valid block ids";
-            let next = self
+            self.time = time;
+            let mut block = self
                 .blocks
-                .get(block_id)
-                .expect(expect_message)
-                .next(&self.blocks);
-            let block = self.blocks.get_mut(block_id).expect(expect_message);
-            match event_type {
-                EventType::In => block.process_in(id, &mut self.event_queue, time),
-                EventType::Out => {
-                    block.process_out(id, &mut self.event_queue, time);
-                    if let Some(next) = next {
-                        self.event_queue.push(Event(time, next, EventType::In, id));
-                    }
+                .remove(block_id)
+                .ok_or(SimulationError::UnknownBlock(block_id))?;
+            let mut deliver_to = None;
+            let result = match event_type {
+                EventType::In => {
+                    block.process_in(id, &mut self.entities, &mut self.event_queue, time)
                 }
-            }
+                EventType::Timeout => {
+                    block.process_timeout(id, &mut self.entities, &mut self.event_queue, time)
+                }
+                EventType::Out => match self.entities.get_mut(id) {
+                    Some(entity) => {
+                        let next = block.next(entity, &self.blocks);
+                        let blocking = block.blocking();
+                        let full = next
+                            .and_then(|next| self.blocks.get(next))
+                            .is_some_and(|next| !next.accepts());
+                        match next {
+                            Some(next) if blocking != Blocking::Reject && full => {
+                                self.blocked.push(BlockedEntity {
+                                    block: block_id,
+                                    entity_id: id,
+                                    next,
+                                });
+                                block.block(id, time)
+                            }
+                            _ => {
+                                let result = block.process_out(
+                                    id,
+                                    &mut self.entities,
+                                    &mut self.event_queue,
+                                    time,
+                                );
+                                match next {
+                                    Some(next) if blocking != Blocking::Reject => {
+                                        deliver_to = Some(next);
+                                    }
+                                    Some(next) => {
+                                        self.event_queue.push(Event(time, next, EventType::In, id));
+                                    }
+                                    None => {
+                                        self.entities.remove(id);
+                                    }
+                                }
+                                result
+                            }
+                        }
+                    }
+                    None => Err(SimulationError::UnknownEntity(id)),
+                },
+            };
+            self.blocks.insert(block_id, block);
+            result?;
             (self.on_simulation_step)(self, Event(time, block_id, event_type, id));
+            if let Some(next) = deliver_to {
+                self.deliver(next, id, time)?;
+            }
+            self.unblock(time, &before_service)?;
+            self.jockey(time)?;
+        }
+        for block in self.blocks.values_mut() {
+            block.finish(duration);
+        }
+        self.time = duration;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blocks::{Blocking, CreateBlock, DisposeBlock, ProcessBlock, ProcessBlockStats},
+        distributions::Deterministic,
+        queue::Queue,
+        routers::{DirectRouter, ProbabilityRouter},
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn lane(id: BlockId) -> impl Block {
+        lane_with(id, Queue::default())
+    }
+
+    fn lane_with(id: BlockId, queue: Queue) -> impl Block {
+        ProcessBlock::builder(id)
+            .distribution(Deterministic::new(3.0))
+            .queue(queue)
+            .router(DirectRouter::new("dispose"))
+            .build()
+    }
+
+    fn process_stats(network: &QueueNetwork, block_id: BlockId) -> ProcessBlockStats {
+        match network.blocks[block_id].stats() {
+            BlockStats::Process(stats) => *stats,
+            _ => panic!("{block_id} is not a process block"),
+        }
+    }
+
+    /// Arrivals every second at lane `a`, next to lane `b`.
+    fn lanes(b: impl Block + 'static) -> QueueNetwork {
+        QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(DirectRouter::new("a"))
+                    .build(),
+            )
+            .add_block(lane("a"))
+            .add_block(b)
+            .add_block(DisposeBlock::new("dispose"))
+    }
+
+    #[test]
+    fn lane_only_reached_by_jockeying_is_reachable() {
+        assert_eq!(
+            lanes(lane("b")).validate(),
+            Err(vec![ValidationError::Unreachable("b")])
+        );
+        let mut network = lanes(lane("b")).jockeying(&["a", "b"], 1);
+        assert_eq!(network.validate(), Ok(()));
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let BlockStats::Process(b) = &network.stats()["b"] else {
+            panic!("b is not a process block");
+        };
+        assert!(b.jockeys_in > 0);
+        assert!(b.processed > 0);
+    }
+
+    #[test]
+    fn jockeying_balances_lanes() {
+        let mut network = lanes(lane("b")).jockeying(&["a", "b"], 1);
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let (a, b) = (process_stats(&network, "a"), process_stats(&network, "b"));
+        assert_eq!((a.jockeys_out, a.jockeys_in), (9, 0));
+        assert_eq!((b.jockeys_in, b.jockeys_out), (9, 0));
+        assert_eq!((a.processed, b.processed), (6, 5));
+        assert_eq!(a.queue.unwrap().final_length, 4);
+        assert_eq!(b.queue.unwrap().final_length, 3);
+    }
+
+    #[test]
+    fn jockeying_respects_destination_capacity() {
+        let mut network = lanes(lane_with("b", Queue::from_capacity(1))).jockeying(&["a", "b"], 1);
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let (a, b) = (process_stats(&network, "a"), process_stats(&network, "b"));
+        assert_eq!((a.jockeys_out, b.jockeys_in), (7, 7));
+        assert_eq!((a.processed, b.processed), (6, 5));
+        assert_eq!(a.queue.unwrap().final_length, 6);
+        let b_queue = b.queue.unwrap();
+        assert_eq!((b_queue.final_length, b_queue.max_length), (1, 1));
+        assert_eq!(b.rejections, 0);
+    }
+
+    #[test]
+    fn time_weighted_stats_cover_the_whole_window() {
+        let mut network = QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(DirectRouter::new("process"))
+                    .build(),
+            )
+            .add_block(
+                ProcessBlock::builder("process")
+                    .distribution(Deterministic::new(0.5))
+                    .queue(Queue::default())
+                    .router(DirectRouter::new("dispose"))
+                    .build(),
+            )
+            .add_block(DisposeBlock::new("dispose"))
+            .warm_up(Duration::from_secs(50));
+        network.simulate(Duration::from_secs(100)).unwrap();
+        let process = process_stats(&network, "process");
+        assert_eq!(process.devices.average_workload, 0.5);
+        assert_eq!(process.queue.unwrap().average_length, 0.0);
+    }
+
+    /// Arrivals every second at a block serving in two seconds, so that its
+    /// queue keeps growing.
+    fn overloaded() -> QueueNetwork {
+        QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(DirectRouter::new("process"))
+                    .build(),
+            )
+            .add_block(
+                ProcessBlock::builder("process")
+                    .distribution(Deterministic::new(2.0))
+                    .queue(Queue::default())
+                    .router(DirectRouter::new("dispose"))
+                    .build(),
+            )
+            .add_block(DisposeBlock::new("dispose"))
+    }
+
+    #[test]
+    fn warm_up_discards_statistics_before_it_ends() {
+        let mut network = overloaded().warm_up(Duration::from_secs(50));
+        network.simulate(Duration::from_secs(100)).unwrap();
+        let stats = network.stats();
+        let BlockStats::Create(create) = &stats["create"] else {
+            panic!("create is not a create block");
+        };
+        let BlockStats::Dispose(dispose) = &stats["dispose"] else {
+            panic!("dispose is not a dispose block");
+        };
+        let process = process_stats(&network, "process");
+        let queue = process.queue.unwrap();
+        assert_eq!(create.created_events, 50);
+        assert_eq!((process.processed, dispose.disposed_events), (25, 25));
+        assert_eq!(queue.wait_time.count, 25);
+        assert_eq!((queue.min_length, queue.max_length), (24, 50));
+        assert_eq!(queue.average_length, 37.5);
+        assert_eq!(process.devices.average_workload, 1.0);
+
+        let mut network = overloaded();
+        network.simulate(Duration::from_secs(100)).unwrap();
+        let queue = process_stats(&network, "process").queue.unwrap();
+        assert_eq!(queue.average_length, 25.0);
+    }
+
+    #[test]
+    fn warm_up_must_end_before_the_simulation() {
+        for warm_up in [100, 150] {
+            let mut network = overloaded().warm_up(Duration::from_secs(warm_up));
+            assert!(matches!(
+                network.simulate(Duration::from_secs(100)),
+                Err(SimulationError::WarmUpTooLong { .. })
+            ));
         }
     }
+
+    /// Arrivals every second at `first`, serving in one second, in front of
+    /// `second`, serving in two seconds without a queue.
+    fn tandem(blocking: Blocking) -> QueueNetwork {
+        QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(DirectRouter::new("first"))
+                    .build(),
+            )
+            .add_block(
+                ProcessBlock::builder("first")
+                    .distribution(Deterministic::new(1.0))
+                    .queue(Queue::default())
+                    .blocking(blocking)
+                    .router(DirectRouter::new("second"))
+                    .build(),
+            )
+            .add_block(
+                ProcessBlock::builder("second")
+                    .distribution(Deterministic::new(2.0))
+                    .router(DirectRouter::new("dispose"))
+                    .build(),
+            )
+            .add_block(DisposeBlock::new("dispose"))
+    }
+
+    #[test]
+    fn blocking_after_service_holds_served_entities() {
+        let mut network = tandem(Blocking::AfterService);
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let (first, second) = (
+            process_stats(&network, "first"),
+            process_stats(&network, "second"),
+        );
+        // The first device is blocked from 2 to 3, 4 to 5, ..., 18 to 19.
+        assert_eq!((first.rejections, second.rejections), (0, 0));
+        assert_eq!((first.processed, second.processed), (10, 9));
+        assert_eq!(first.devices.blocked_time, 9.0);
+    }
+
+    #[test]
+    fn blocking_before_service_holds_entities_unserved() {
+        let mut network = tandem(Blocking::BeforeService);
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let (first, second) = (
+            process_stats(&network, "first"),
+            process_stats(&network, "second"),
+        );
+        // Services at the first block run from 0 to 1, then 3 to 4, 6 to 7,
+        // ..., each entity holding the device for the two seconds before and
+        // the last one from 19 until the end of the run.
+        assert_eq!((first.rejections, second.rejections), (0, 0));
+        assert_eq!((first.processed, second.processed), (7, 6));
+        assert_eq!(first.devices.blocked_time, 13.0);
+    }
+
+    #[test]
+    fn event_priority_orders_simultaneous_events() {
+        // Every arrival coincides with the end of the previous service.
+        let network = || {
+            QueueNetwork::new()
+                .add_block(source("process"))
+                .add_block(
+                    ProcessBlock::builder("process")
+                        .distribution(Deterministic::new(1.0))
+                        .router(DirectRouter::new("dispose"))
+                        .build(),
+                )
+                .add_block(DisposeBlock::new("dispose"))
+        };
+        let mut in_order = network();
+        in_order.simulate(Duration::from_secs(10)).unwrap();
+        let process = process_stats(&in_order, "process");
+        assert_eq!((process.processed, process.rejections), (9, 0));
+
+        let mut arrivals_first = network().event_priority(|event| match event.2 {
+            EventType::In => 1,
+            _ => 0,
+        });
+        arrivals_first.simulate(Duration::from_secs(10)).unwrap();
+        // Every other arrival finds the device still busy.
+        let process = process_stats(&arrivals_first, "process");
+        assert_eq!((process.processed, process.rejections), (5, 5));
+    }
+
+    #[test]
+    fn direct_deliveries_reach_the_step_callback() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut network = tandem(Blocking::AfterService)
+            .on_simulation_step(move |_, event| recorded.lock().unwrap().push(event));
+        network.simulate(Duration::from_secs(20)).unwrap();
+        let arrivals = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|Event(_, block, event_type, _)| {
+                *block == "second" && *event_type == EventType::In
+            })
+            .count();
+        assert_eq!(arrivals, process_stats(&network, "second").processed + 1);
+    }
+
+    fn source(next: BlockId) -> impl Block {
+        CreateBlock::builder("create")
+            .distribution(Deterministic::new(1.0))
+            .router(DirectRouter::new(next))
+            .build()
+    }
+
+    fn errors(network: QueueNetwork) -> Vec<ValidationError> {
+        network.validate().unwrap_err()
+    }
+
+    #[test]
+    fn validate_reports_duplicate_blocks() {
+        let network = QueueNetwork::new()
+            .add_block(source("dispose"))
+            .add_block(DisposeBlock::new("dispose"))
+            .add_block(DisposeBlock::new("dispose"));
+        assert_eq!(
+            errors(network),
+            [ValidationError::DuplicateBlock("dispose")]
+        );
+    }
+
+    #[test]
+    fn validate_reports_dangling_targets() {
+        let network = QueueNetwork::new().add_block(source("missing"));
+        assert_eq!(
+            errors(network),
+            [ValidationError::DanglingTarget {
+                block: "create",
+                target: "missing",
+            }]
+        );
+    }
+
+    #[test]
+    fn validate_reports_unreachable_blocks() {
+        let network = QueueNetwork::new()
+            .add_block(source("dispose"))
+            .add_block(lane("orphan"))
+            .add_block(DisposeBlock::new("dispose"));
+        assert_eq!(errors(network), [ValidationError::Unreachable("orphan")]);
+    }
+
+    #[test]
+    fn validate_reports_blocks_without_path_to_dispose() {
+        let network = QueueNetwork::new().add_block(source("loop")).add_block(
+            ProcessBlock::builder("loop")
+                .distribution(Deterministic::new(1.0))
+                .router(DirectRouter::new("loop"))
+                .build(),
+        );
+        assert_eq!(errors(network), [ValidationError::NoPathToDispose("loop")]);
+    }
+
+    #[test]
+    fn validate_reports_invalid_weights() {
+        let network = QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Deterministic::new(1.0))
+                    .router(ProbabilityRouter::new(&[(0.0, "a"), (1.0, "dispose")]))
+                    .build(),
+            )
+            .add_block(lane("a"))
+            .add_block(DisposeBlock::new("dispose"));
+        assert_eq!(
+            errors(network),
+            [ValidationError::InvalidWeight {
+                block: "create",
+                target: "a",
+                weight: 0.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn validate_reports_blocks_not_jockeyable() {
+        let network = lanes(lane("b")).jockeying(&["a", "b", "dispose", "ghost"], 1);
+        assert_eq!(
+            errors(network),
+            [
+                ValidationError::NotJockeyable("dispose"),
+                ValidationError::NotJockeyable("ghost"),
+            ]
+        );
+    }
 }
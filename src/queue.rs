@@ -1,70 +1,452 @@
+//! Bounded queues in front of the devices of a process block.
+
 use crate::{
-    stats::{Stats, StepStats},
-    weighted_average::weighted_average,
+    disciplines::{Fifo, QueueDiscipline},
+    entity::Entity,
+    error::SimulationError,
+    samples::{LevelDistribution, LevelStats, SampleStats, Samples},
+    stats::{prefixed, Metrics, Stats, StepStats},
+    time_weighted::TimeWeighted,
 };
-use std::{collections::VecDeque, fmt::Debug, time::Duration};
+use serde::Serialize;
+use std::{collections::VecDeque, time::Duration};
 
-#[derive(Default)]
+/// Queue of entities waiting for a free device, optionally bounded. The next
+/// entity to serve is chosen by a [`QueueDiscipline`], FIFO by default. After
+/// [`Queue::priority`] the discipline only chooses among the entities of the
+/// highest priority class waiting.
+///
+/// Every change in length updates the time-weighted statistics in `lengths`,
+/// starting with the length at time zero. Entity ids are stored
+/// with the time they were enqueued at, and the wait of every dequeued entity
+/// is recorded in `waits`.
 pub struct Queue {
-    queue: VecDeque<usize>,
+    queue: VecDeque<QueueEntry>,
+    discipline: Box<dyn QueueDiscipline>,
+    /// Most entities waiting at once, unbounded if `None`.
     pub capacity: Option<usize>,
-    pub lengths: Vec<(Duration, usize)>,
+    /// Whether higher priority classes are served first.
+    pub priority: bool,
+    /// Length over time.
+    pub lengths: TimeWeighted,
+    /// Time every dequeued entity spent in the queue, in seconds, in the
+    /// order they left it.
+    pub waits: Samples,
+    /// Time spent at every length.
+    pub length_distribution: LevelDistribution,
+}
+
+/// An entity waiting in a [`Queue`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueueEntry {
+    /// Id of the waiting entity.
+    pub entity_id: usize,
+    /// Priority class of the entity when it was enqueued.
+    pub priority: i32,
+    /// Simulated time the entity joined the queue at.
+    pub enqueued_at: Duration,
+    /// Service time sampled when the entity was enqueued, if the discipline
+    /// needed it.
+    pub service_time: Option<Duration>,
+    /// Rank given by the discipline, see [`QueueDiscipline::rank`].
+    pub rank: f64,
+    /// Whether the entity was returned after a preempted service, in which
+    /// case `service_time` is the time it still needs.
+    pub preempted: bool,
 }
 
-#[derive(Debug)]
+/// Statistics of a [`Queue`] over the whole simulation.
+#[derive(Debug, Clone, Serialize)]
 pub struct QueueStats {
-    final_length: usize,
-    average_length: f32,
+    /// Length at the end of the simulation.
+    pub final_length: usize,
+    /// Time-weighted average length.
+    pub average_length: f32,
+    /// Time-weighted variance of the length.
+    pub length_variance: f32,
+    /// Shortest length reached.
+    pub min_length: usize,
+    /// Longest length reached.
+    pub max_length: usize,
+    /// Time entities that had to queue spent in the queue. Entities still in
+    /// the queue are not included.
+    pub wait_time: SampleStats,
+    /// Time-weighted distribution of the length.
+    pub length: LevelStats,
 }
 
-#[derive(Debug)]
+/// Instantaneous state of a [`Queue`].
+#[derive(Debug, Clone, Serialize)]
 pub struct QueueStepStats {
-    length: usize,
+    /// Number of waiting entities.
+    pub length: usize,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            discipline: Box::new(Fifo),
+            capacity: None,
+            priority: false,
+            lengths: TimeWeighted::new(0.0, Duration::ZERO),
+            waits: Samples::new(),
+            length_distribution: LevelDistribution::new(0, Duration::ZERO),
+        }
+    }
 }
 
 impl Queue {
+    /// Creates a first-in first-out queue holding at most `capacity`
+    /// entities.
     pub fn from_capacity(capacity: usize) -> Self {
         Self {
             queue: VecDeque::with_capacity(capacity),
             capacity: Some(capacity),
-            lengths: Vec::new(),
+            ..Self::default()
         }
     }
+
+    /// Sets the order in which waiting entities are served.
+    pub fn discipline(mut self, discipline: impl QueueDiscipline + 'static) -> Self {
+        self.discipline = Box::new(discipline);
+        self
+    }
+
+    /// Serves higher priority classes first, see [`Entity::priority`].
+    pub fn priority(mut self) -> Self {
+        self.priority = true;
+        self
+    }
+
+    /// Records every change in length in the history of `lengths`, which
+    /// otherwise keeps constant memory.
+    pub fn record_history(mut self) -> Self {
+        self.lengths = self.lengths.record_history();
+        self
+    }
+
+    /// Sets how waits are summarized, by P² by default.
+    pub fn waits(mut self, waits: Samples) -> Self {
+        self.waits = waits;
+        self
+    }
+
+    /// Sets the reported percentiles of the time-weighted length.
+    pub fn length_percentiles(mut self, percentiles: &[f64]) -> Self {
+        self.length_distribution = self.length_distribution.percentiles(percentiles);
+        self
+    }
 }
 
 impl Queue {
+    /// Number of waiting entities.
     pub fn len(&self) -> usize {
         self.queue.len()
     }
 
-    pub fn enqueue(&mut self, event_id: usize, simulation_duration: Duration) {
-        self.queue.push_back(event_id);
-        self.lengths.push((simulation_duration, self.queue.len()));
+    /// Whether no entity is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
     }
 
-    pub fn dequeue(&mut self, simulation_duration: Duration) -> usize {
-        let event_id = self
+    /// Whether the queue is at capacity, never for an unbounded queue.
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.capacity.unwrap_or(usize::MAX)
+    }
+
+    /// Whether entities need their service time sampled when they join the
+    /// queue, see [`QueueDiscipline::presamples_service_time`].
+    pub fn presamples_service_time(&self) -> bool {
+        self.discipline.presamples_service_time()
+    }
+
+    /// Reseeds the random stream of the discipline.
+    pub fn seed(&mut self, seed: u64) {
+        self.discipline.seed(seed);
+    }
+
+    /// Discards the statistics of lengths and waits, restarting the lengths
+    /// from the current length at `simulation_duration`.
+    pub fn reset_stats(&mut self, simulation_duration: Duration) {
+        self.lengths.reset(simulation_duration);
+        self.waits.clear();
+        self.length_distribution.reset(simulation_duration);
+    }
+
+    /// Adds `entity`, with its service time if sampled in advance.
+    pub fn enqueue(
+        &mut self,
+        entity: &Entity,
+        service_time: Option<Duration>,
+        simulation_duration: Duration,
+    ) {
+        self.transfer(
+            entity,
+            QueueEntry {
+                entity_id: entity.id,
+                priority: entity.priority,
+                enqueued_at: simulation_duration,
+                service_time,
+                rank: 0.0,
+                preempted: false,
+            },
+            simulation_duration,
+        );
+    }
+
+    /// Adds `entity` behind its class with the waiting time, service time and
+    /// preemption of `entry`, e.g. after moving from another queue. The rank
+    /// is given anew by the discipline of this queue.
+    pub fn transfer(&mut self, entity: &Entity, entry: QueueEntry, simulation_duration: Duration) {
+        let entry = QueueEntry {
+            priority: entity.priority,
+            rank: self.discipline.rank(entity, entry.service_time),
+            ..entry
+        };
+        let position = if self.priority {
+            self.queue
+                .iter()
+                .position(|queued| queued.priority < entry.priority)
+                .unwrap_or(self.queue.len())
+        } else {
+            self.queue.len()
+        };
+        self.insert(position, entry, simulation_duration);
+    }
+
+    /// Returns `entity` to the head of its class, e.g. after its service was
+    /// preempted. Ignores the capacity.
+    pub fn requeue(
+        &mut self,
+        entity: &Entity,
+        service_time: Option<Duration>,
+        simulation_duration: Duration,
+    ) {
+        let entry = QueueEntry {
+            entity_id: entity.id,
+            priority: entity.priority,
+            enqueued_at: simulation_duration,
+            service_time,
+            rank: self.discipline.rank(entity, service_time),
+            preempted: true,
+        };
+        let position = if self.priority {
+            self.queue
+                .iter()
+                .position(|queued| queued.priority <= entry.priority)
+                .unwrap_or(self.queue.len())
+        } else {
+            0
+        };
+        self.insert(position, entry, simulation_duration);
+    }
+
+    /// Removes `entity_id` wherever it waits, e.g. when it abandons the queue.
+    /// Its wait is not recorded in `waits`.
+    pub fn remove(
+        &mut self,
+        entity_id: usize,
+        simulation_duration: Duration,
+    ) -> Option<QueueEntry> {
+        let position = self
             .queue
-            .pop_front()
-            .expect("queue should not be empty when dequeueing an event");
-        self.lengths.push((simulation_duration, self.queue.len()));
-        event_id
+            .iter()
+            .position(|entry| entry.entity_id == entity_id)?;
+        let entry = self.queue.remove(position)?;
+        self.record_length(simulation_duration);
+        Some(entry)
+    }
+
+    /// Removes the entity the discipline would serve last, from the lowest
+    /// class when priority is on. Its wait is not recorded in `waits`.
+    pub fn remove_last(&mut self, simulation_duration: Duration) -> Option<QueueEntry> {
+        let waiting = self.queue.make_contiguous();
+        let last = waiting.last()?;
+        let start = if self.priority {
+            waiting
+                .iter()
+                .position(|entry| entry.priority == last.priority)
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let index = start + self.discipline.last(&waiting[start..]);
+        let entry = self.queue.remove(index)?;
+        self.record_length(simulation_duration);
+        Some(entry)
+    }
+
+    fn insert(&mut self, position: usize, entry: QueueEntry, simulation_duration: Duration) {
+        self.queue.insert(position, entry);
+        self.record_length(simulation_duration);
+    }
+
+    fn record_length(&mut self, simulation_duration: Duration) {
+        self.lengths
+            .set(self.queue.len() as f64, simulation_duration);
+        self.length_distribution
+            .set(self.queue.len(), simulation_duration);
+    }
+
+    /// Extends the length statistics until the end of the simulation at
+    /// `simulation_duration`.
+    pub fn finish(&mut self, simulation_duration: Duration) {
+        self.lengths.advance_to(simulation_duration);
+        self.length_distribution.advance_to(simulation_duration);
+    }
+
+    /// Removes the next entity to serve.
+    pub fn dequeue(
+        &mut self,
+        simulation_duration: Duration,
+    ) -> Result<QueueEntry, SimulationError> {
+        let waiting = self.queue.make_contiguous();
+        let Some(first) = waiting.first() else {
+            return Err(SimulationError::EmptyQueue);
+        };
+        let candidates = if self.priority {
+            waiting
+                .iter()
+                .take_while(|entry| entry.priority == first.priority)
+                .count()
+        } else {
+            waiting.len()
+        };
+        let index = self.discipline.select(&waiting[..candidates]);
+        let entry = self
+            .queue
+            .remove(index)
+            .ok_or(SimulationError::EmptyQueue)?;
+        self.record_length(simulation_duration);
+        self.waits
+            .push((simulation_duration - entry.enqueued_at).as_secs_f64());
+        Ok(entry)
+    }
+}
+
+impl<Q: QueueDiscipline + 'static> From<Q> for Queue {
+    fn from(discipline: Q) -> Self {
+        Self::default().discipline(discipline)
     }
 }
 
 impl Stats for Queue {
-    fn stats(&self) -> Box<dyn Debug> {
-        Box::new(QueueStats {
+    type Output = QueueStats;
+
+    fn stats(&self) -> QueueStats {
+        let lengths = self.lengths.stats();
+        QueueStats {
             final_length: self.queue.len(),
-            average_length: weighted_average(&self.lengths),
-        })
+            average_length: lengths.average as f32,
+            length_variance: lengths.variance as f32,
+            min_length: lengths.min as usize,
+            max_length: lengths.max as usize,
+            wait_time: self.waits.stats(),
+            length: self.length_distribution.stats(),
+        }
     }
 }
 
 impl StepStats for Queue {
-    fn step_stats(&self) -> Box<dyn Debug> {
-        Box::new(QueueStepStats {
+    type Output = QueueStepStats;
+
+    fn step_stats(&self) -> QueueStepStats {
+        QueueStepStats {
             length: self.queue.len(),
-        })
+        }
+    }
+}
+
+impl Metrics for QueueStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![
+            ("final_length".into(), self.final_length as f64),
+            ("average_length".into(), self.average_length as f64),
+            ("length_variance".into(), self.length_variance as f64),
+            ("min_length".into(), self.min_length as f64),
+            ("max_length".into(), self.max_length as f64),
+        ];
+        metrics.extend(prefixed("wait_time", &self.wait_time));
+        metrics.extend(prefixed("length", &self.length));
+        metrics
+    }
+}
+
+impl Metrics for QueueStepStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        vec![("length".into(), self.length as f64)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disciplines::{Lifo, ShortestJobFirst};
+
+    /// Enqueues entities `0..service_times.len()` one second apart, entity
+    /// `i` with priority `priorities[i]` and service time `service_times[i]`.
+    fn fill(mut queue: Queue, priorities: &[i32], service_times: &[u64]) -> Queue {
+        for (i, (&priority, &service_time)) in priorities.iter().zip(service_times).enumerate() {
+            let mut entity = Entity::new(i, Duration::ZERO);
+            entity.priority = priority;
+            queue.enqueue(
+                &entity,
+                Some(Duration::from_secs(service_time)),
+                Duration::from_secs(i as u64),
+            );
+        }
+        queue
+    }
+
+    fn last_id(queue: &mut Queue) -> usize {
+        queue
+            .remove_last(Duration::from_secs(10))
+            .unwrap()
+            .entity_id
+    }
+
+    #[test]
+    fn fifo_moves_the_newest_arrival() {
+        let mut queue = fill(Queue::default(), &[0; 3], &[1, 2, 3]);
+        assert_eq!(last_id(&mut queue), 2);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn lifo_moves_the_oldest_arrival() {
+        let mut queue = fill(Queue::from(Lifo), &[0; 3], &[1, 2, 3]);
+        assert_eq!(last_id(&mut queue), 0);
+    }
+
+    #[test]
+    fn shortest_job_first_moves_the_longest_job() {
+        let mut queue = fill(Queue::from(ShortestJobFirst), &[0; 3], &[1, 5, 3]);
+        assert_eq!(last_id(&mut queue), 1);
+    }
+
+    #[test]
+    fn priority_moves_from_the_lowest_class() {
+        let mut queue = fill(Queue::default().priority(), &[0, 1, 0, 1], &[1; 4]);
+        assert_eq!(last_id(&mut queue), 2);
+        assert_eq!(last_id(&mut queue), 0);
+        assert_eq!(last_id(&mut queue), 3);
+    }
+
+    #[test]
+    fn transfer_keeps_the_entry_of_a_preempted_entity() {
+        let entity = Entity::new(7, Duration::ZERO);
+        let entry = QueueEntry {
+            entity_id: 7,
+            priority: 0,
+            enqueued_at: Duration::from_secs(2),
+            service_time: Some(Duration::from_secs(8)),
+            rank: 0.0,
+            preempted: true,
+        };
+        let mut queue = Queue::default();
+        queue.transfer(&entity, entry, Duration::from_secs(5));
+        assert_eq!(queue.dequeue(Duration::from_secs(6)).unwrap(), entry);
+        assert_eq!(queue.waits.stats().mean, 4.0);
     }
 }
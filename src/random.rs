@@ -0,0 +1,30 @@
+//! Seeding of the independent random number streams of a simulation.
+
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Random number generator owned by every block and router.
+pub type SimulationRng = StdRng;
+
+/// Derives the seed of an independent stream named `stream` from `seed`.
+///
+/// The result only depends on its arguments, so a block gets the same stream
+/// regardless of the order in which blocks were added to the network.
+pub fn stream_seed(seed: u64, stream: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in stream.bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    splitmix64(seed ^ hash)
+}
+
+/// Generator used until the network seeds the block.
+pub fn unseeded_rng() -> SimulationRng {
+    SimulationRng::seed_from_u64(0)
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
@@ -0,0 +1,226 @@
+//! Independent replications of a simulation and their aggregated
+//! estimates.
+
+use crate::{
+    batch_means::BatchMeans,
+    blocks::{BlockId, BlockStats},
+    error::{ReplicationError, SimulationError},
+    estimation::Estimate,
+    network::QueueNetwork,
+    random::stream_seed,
+    stats::Metrics,
+};
+use rand::{rng, Rng};
+use rayon::{prelude::*, ThreadPoolBuilder};
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+
+/// Runs independent replications of a network and aggregates their
+/// statistics.
+///
+/// Replications can run on a thread pool. Every replication builds its own
+/// network and gets its seed from its index, and results are kept in
+/// replication order, so the report does not depend on the number of threads.
+pub struct Replications {
+    count: usize,
+    seed: Option<u64>,
+    confidence: f64,
+    threads: usize,
+}
+
+/// Final statistics of a single replication.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationRun {
+    /// Seed the replication ran with, to replay it on its own.
+    pub seed: u64,
+    /// Final statistics of every block.
+    pub stats: BTreeMap<BlockId, BlockStats>,
+    /// Steady-state estimates from this replication alone, see
+    /// [`QueueNetwork::batch_means`].
+    pub batch_means: BTreeMap<BlockId, Vec<(String, BatchMeans)>>,
+}
+
+/// Statistics of every replication and their estimates across replications.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplicationReport {
+    /// Every replication, in order.
+    pub runs: Vec<ReplicationRun>,
+    /// Estimate of every [`Metrics`] value of every block across the runs.
+    pub summary: BTreeMap<BlockId, Vec<(String, Estimate)>>,
+}
+
+impl Replications {
+    /// Runs `count` replications on the current thread, with 95% confidence
+    /// intervals.
+    pub fn new(count: usize) -> Self {
+        Self {
+            count,
+            seed: None,
+            confidence: 0.95,
+            threads: 1,
+        }
+    }
+
+    /// Seeds the first replication; the seeds of the others are derived from
+    /// it with [`replication_seed`]. Drawn at random when not set.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Confidence level of the intervals, 0.95 by default. Must lie strictly
+    /// between 0 and 1.
+    pub fn confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Number of replications simulated in parallel, 1 by default. 0 uses
+    /// every available core.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Simulates a network built by `network` for every replication,
+    /// overriding its seed. Fails without any replication to run or with a
+    /// confidence level outside (0, 1).
+    pub fn run(
+        &self,
+        network: impl Fn() -> QueueNetwork + Sync,
+        duration: Duration,
+    ) -> Result<ReplicationReport, ReplicationError> {
+        if self.count == 0 {
+            return Err(ReplicationError::NoReplications);
+        }
+        if !(self.confidence > 0.0 && self.confidence < 1.0) {
+            return Err(ReplicationError::InvalidConfidence(self.confidence));
+        }
+        let seed = self.seed.unwrap_or_else(|| rng().random());
+        let replicate = |replication| {
+            let seed = replication_seed(seed, replication);
+            let mut network = network().seed(seed);
+            network.simulate(duration)?;
+            Ok(ReplicationRun {
+                seed,
+                stats: network.stats(),
+                batch_means: network.batch_means(self.confidence),
+            })
+        };
+        let runs = if self.threads == 1 {
+            (0..self.count)
+                .map(replicate)
+                .collect::<Result<Vec<_>, SimulationError>>()?
+        } else {
+            ThreadPoolBuilder::new()
+                .num_threads(self.threads)
+                .build()
+                .map_err(ReplicationError::ThreadPool)?
+                .install(|| {
+                    (0..self.count)
+                        .into_par_iter()
+                        .map(replicate)
+                        .collect::<Result<Vec<_>, SimulationError>>()
+                })?
+        };
+        Ok(ReplicationReport::new(runs, self.confidence))
+    }
+}
+
+impl ReplicationReport {
+    /// Aggregates `runs`, keeping metrics in the order blocks report them.
+    pub fn new(runs: Vec<ReplicationRun>, confidence: f64) -> Self {
+        let mut samples = BTreeMap::<BlockId, Vec<(String, Vec<f64>)>>::new();
+        for run in &runs {
+            for (&block_id, stats) in &run.stats {
+                let block_samples = samples.entry(block_id).or_default();
+                for (metric, value) in stats.metrics() {
+                    match block_samples.iter_mut().find(|(name, _)| *name == metric) {
+                        Some((_, values)) => values.push(value),
+                        None => block_samples.push((metric, vec![value])),
+                    }
+                }
+            }
+        }
+        let summary = samples
+            .into_iter()
+            .map(|(block_id, metrics)| {
+                let estimates = metrics
+                    .into_iter()
+                    .map(|(metric, values)| (metric, Estimate::from_samples(&values, confidence)))
+                    .collect();
+                (block_id, estimates)
+            })
+            .collect();
+        Self { runs, summary }
+    }
+}
+
+/// Seed of the replication with index `replication`: `seed` itself for the
+/// first one, so that it can be replayed on its own, and an independent
+/// stream derived from `seed` for the others.
+pub fn replication_seed(seed: u64, replication: usize) -> u64 {
+    match replication {
+        0 => seed,
+        _ => stream_seed(seed, &format!("replication {replication}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blocks::{CreateBlock, DisposeBlock, ProcessBlock},
+        queue::Queue,
+        routers::DirectRouter,
+    };
+    use rand_distr::Exp;
+
+    fn network() -> QueueNetwork {
+        QueueNetwork::new()
+            .add_block(
+                CreateBlock::builder("create")
+                    .distribution(Exp::new(1.0).unwrap())
+                    .router(DirectRouter::new("process"))
+                    .build(),
+            )
+            .add_block(
+                ProcessBlock::builder("process")
+                    .distribution(Exp::new(1.25).unwrap())
+                    .queue(Queue::default())
+                    .router(DirectRouter::new("dispose"))
+                    .build(),
+            )
+            .add_block(DisposeBlock::new("dispose"))
+    }
+
+    #[test]
+    fn results_do_not_depend_on_the_number_of_threads() {
+        let report = |threads| {
+            let report = Replications::new(4)
+                .seed(42)
+                .threads(threads)
+                .run(network, Duration::from_secs(200))
+                .unwrap();
+            format!("{report:?}")
+        };
+        assert_eq!(report(1), report(4));
+    }
+
+    #[test]
+    fn run_rejects_confidence_outside_zero_and_one() {
+        for confidence in [0.0, 1.0, 1.5, -0.5, f64::NAN] {
+            let result = Replications::new(2)
+                .confidence(confidence)
+                .run(network, Duration::from_secs(10));
+            assert!(matches!(
+                result,
+                Err(ReplicationError::InvalidConfidence(_))
+            ));
+        }
+        assert!(Replications::new(2)
+            .confidence(0.99)
+            .run(network, Duration::from_secs(10))
+            .is_ok());
+    }
+}
@@ -1,21 +1,32 @@
 use crate::{
     blocks::{Block, BlockId},
+    entity::Entity,
     routers::Router,
 };
 use std::collections::HashMap;
 
+/// Always routes to the same block.
 pub struct DirectRouter {
     next: BlockId,
 }
 
 impl DirectRouter {
+    /// Routes every entity to `next`.
     pub fn new(next: BlockId) -> Self {
         Self { next }
     }
 }
 
 impl Router for DirectRouter {
-    fn next(&self, _blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId> {
+    fn next(
+        &mut self,
+        _entity: &mut Entity,
+        _blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
         Some(self.next)
     }
+
+    fn targets(&self) -> Vec<BlockId> {
+        vec![self.next]
+    }
 }
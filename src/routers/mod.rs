@@ -1,11 +1,52 @@
+//! Choice of the block an entity moves to next.
+
 mod direct;
 mod probability;
 
-use crate::blocks::{Block, BlockId};
+use crate::{
+    blocks::{Block, BlockId},
+    entity::Entity,
+    error::ValidationError,
+};
 pub use direct::DirectRouter;
 pub use probability::ProbabilityRouter;
 use std::collections::HashMap;
 
-pub trait Router {
-    fn next(&self, blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId>;
+/// Chooses the next block for an entity leaving a block.
+pub trait Router: Send {
+    /// `blocks` holds every block of the network except the one being routed
+    /// from. Routers may read and update the attributes of `entity`.
+    fn next(
+        &mut self,
+        entity: &mut Entity,
+        blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId>;
+    /// Every block this router can route to.
+    fn targets(&self) -> Vec<BlockId>;
+    /// Reports configuration problems of the router used by `block`.
+    fn validate(&self, _block: BlockId, _errors: &mut Vec<ValidationError>) {}
+    /// Reseeds the random stream of the router, if it has one.
+    fn seed(&mut self, _seed: u64) {}
+}
+
+impl<R: Router + ?Sized> Router for Box<R> {
+    fn next(
+        &mut self,
+        entity: &mut Entity,
+        blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
+        (**self).next(entity, blocks)
+    }
+
+    fn targets(&self) -> Vec<BlockId> {
+        (**self).targets()
+    }
+
+    fn validate(&self, block: BlockId, errors: &mut Vec<ValidationError>) {
+        (**self).validate(block, errors)
+    }
+
+    fn seed(&mut self, seed: u64) {
+        (**self).seed(seed)
+    }
 }
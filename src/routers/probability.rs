@@ -1,26 +1,40 @@
 use crate::{
     blocks::{Block, BlockId},
+    entity::Entity,
+    error::ValidationError,
+    random::{unseeded_rng, SimulationRng},
     routers::Router,
 };
-use rand::{rng, Rng};
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 
+/// Routes to one of several blocks at random, with probabilities proportional
+/// to the weights.
 pub struct ProbabilityRouter {
     next: Vec<(f32, BlockId)>,
+    total: f32,
+    rng: SimulationRng,
 }
 
 impl ProbabilityRouter {
+    /// Routes to every block of `next` with a probability proportional to
+    /// its weight.
     pub fn new(next: &[(f32, BlockId)]) -> Self {
-        let total = next.iter().map(|(p, _)| p).sum::<f32>();
         Self {
-            next: next.iter().map(|(p, id)| (*p / total, *id)).collect(),
+            next: next.to_vec(),
+            total: next.iter().map(|(p, _)| p).sum::<f32>(),
+            rng: unseeded_rng(),
         }
     }
 }
 
 impl Router for ProbabilityRouter {
-    fn next(&self, _blocks: &HashMap<BlockId, Box<dyn Block>>) -> Option<BlockId> {
-        let random = rng().random::<f32>();
+    fn next(
+        &mut self,
+        _entity: &mut Entity,
+        _blocks: &HashMap<BlockId, Box<dyn Block>>,
+    ) -> Option<BlockId> {
+        let random = self.rng.random::<f32>() * self.total;
 
         let mut sum = 0.0;
         for (probability, block_id) in &self.next {
@@ -32,4 +46,24 @@ impl Router for ProbabilityRouter {
 
         None
     }
+
+    fn targets(&self) -> Vec<BlockId> {
+        self.next.iter().map(|(_, block_id)| *block_id).collect()
+    }
+
+    fn validate(&self, block: BlockId, errors: &mut Vec<ValidationError>) {
+        for &(weight, target) in &self.next {
+            if !(weight.is_finite() && weight > 0.0) {
+                errors.push(ValidationError::InvalidWeight {
+                    block,
+                    target,
+                    weight,
+                });
+            }
+        }
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.rng = SimulationRng::seed_from_u64(seed);
+    }
 }
@@ -0,0 +1,743 @@
+//! Statistics of per-entity observations and of time-weighted levels.
+
+use crate::stats::{Metrics, Stats};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Percentiles reported by default, as probabilities.
+pub const PERCENTILES: [f64; 4] = [0.5, 0.9, 0.95, 0.99];
+
+/// Most batches of observations kept at once. When they are full, adjacent
+/// batches are merged pairwise and their size doubles.
+const MAX_BATCHES: usize = 2048;
+
+/// Observations of a per-entity quantity, e.g. time in system.
+///
+/// Count, mean, variance and extremes are always tracked exactly and in
+/// constant memory. Percentiles come from the selected estimator: the P²
+/// algorithm ([`Samples::p2`], the default), a fixed-width histogram
+/// ([`Samples::histogram`]) or every observation kept in full
+/// ([`Samples::exact`]).
+///
+/// Whatever the estimator, the sums of at most 2048 batches of consecutive
+/// observations are kept for batch-means estimates.
+#[derive(Debug, Clone)]
+pub struct Samples {
+    count: usize,
+    mean: f64,
+    /// Sum of squared deviations from the mean, updated by Welford's method.
+    m2: f64,
+    min: f64,
+    max: f64,
+    percentiles: Vec<f64>,
+    estimator: Estimator,
+    batches: Vec<f64>,
+    batch_size: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Estimator {
+    Exact(Vec<f64>),
+    Histogram(Histogram),
+    P2(Vec<P2Quantile>),
+}
+
+/// Counts of observations in `buckets` buckets of `width` starting at `min`,
+/// plus the observations below and above them.
+#[derive(Debug, Clone)]
+struct Histogram {
+    min: f64,
+    width: f64,
+    counts: Vec<usize>,
+    below: usize,
+    above: usize,
+}
+
+/// Streaming estimate of a single quantile by the P² algorithm of Jain and
+/// Chlamtac, tracking five markers.
+#[derive(Debug, Clone)]
+struct P2Quantile {
+    probability: f64,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+/// Summary of [`Samples`]. Every value but `count` is NaN without samples,
+/// and `variance` is NaN with a single sample.
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleStats {
+    /// Number of observations.
+    pub count: usize,
+    /// Mean of the observations.
+    pub mean: f64,
+    /// Sample variance.
+    pub variance: f64,
+    /// Smallest observation.
+    pub min: f64,
+    /// Largest observation.
+    pub max: f64,
+    /// Estimates of the reported percentiles.
+    pub percentiles: Vec<Percentile>,
+    /// Distribution of the observations, for [`Samples::histogram`] only.
+    /// Not part of the [`Metrics`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Vec<Bucket>>,
+}
+
+/// Value below which a given fraction of the observations falls.
+#[derive(Debug, Clone, Serialize)]
+pub struct Percentile {
+    /// The fraction, e.g. 0.95.
+    pub probability: f64,
+    /// The value, NaN without observations.
+    pub value: f64,
+}
+
+/// Fraction of observations, or of time, with a value in `[lower, upper)`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    /// Inclusive lower bound.
+    pub lower: f64,
+    /// Exclusive upper bound.
+    pub upper: f64,
+    /// Fraction of observations or of time in the bucket.
+    pub fraction: f64,
+}
+
+/// Time-weighted distribution of an integer level, e.g. a queue length: the
+/// time spent at every level, in constant memory per level.
+#[derive(Debug, Clone)]
+pub struct LevelDistribution {
+    times: Vec<f64>,
+    level: usize,
+    since: Duration,
+    percentiles: Vec<f64>,
+}
+
+/// Summary of a [`LevelDistribution`] until its last change.
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelStats {
+    /// Smallest level at or below which the given fraction of time was spent.
+    pub percentiles: Vec<Percentile>,
+    /// Fraction of time spent at every level. Not part of the [`Metrics`].
+    pub distribution: Vec<Bucket>,
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Self::p2()
+    }
+}
+
+impl Samples {
+    /// Creates samples estimating the default percentiles by P².
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps every observation, for exact percentiles. Memory grows with the
+    /// number of observations.
+    pub fn exact() -> Self {
+        Self::with_estimator(Estimator::Exact(Vec::new()))
+    }
+
+    /// Counts observations in `buckets` buckets of `width` starting at `min`,
+    /// reporting the bucketed distribution and percentiles interpolated
+    /// within buckets.
+    pub fn histogram(min: f64, width: f64, buckets: usize) -> Self {
+        Self::with_estimator(Estimator::Histogram(Histogram {
+            min,
+            width,
+            counts: vec![0; buckets],
+            below: 0,
+            above: 0,
+        }))
+    }
+
+    /// Estimates every percentile by the P² algorithm, in constant memory.
+    pub fn p2() -> Self {
+        Self::with_estimator(Estimator::P2(Vec::new())).percentiles(&PERCENTILES)
+    }
+
+    fn with_estimator(estimator: Estimator) -> Self {
+        Self {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            percentiles: PERCENTILES.to_vec(),
+            estimator,
+            batches: Vec::new(),
+            batch_size: 1,
+        }
+    }
+
+    /// Sets the reported percentiles, as probabilities, discarding the
+    /// observations so far.
+    pub fn percentiles(mut self, percentiles: &[f64]) -> Self {
+        self.percentiles = percentiles.to_vec();
+        if let Estimator::P2(quantiles) = &mut self.estimator {
+            *quantiles = percentiles
+                .iter()
+                .map(|&probability| P2Quantile::new(probability))
+                .collect();
+        }
+        self.clear();
+        self
+    }
+
+    /// Records `value`. NaN observations are ignored.
+    pub fn push(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let mut batch = (self.count - 1) / self.batch_size;
+        if batch >= MAX_BATCHES {
+            self.batches = self
+                .batches
+                .chunks(2)
+                .map(|pair| pair.iter().sum())
+                .collect();
+            self.batch_size *= 2;
+            batch /= 2;
+        }
+        if self.batches.len() <= batch {
+            self.batches.push(0.0);
+        }
+        self.batches[batch] += value;
+        match &mut self.estimator {
+            Estimator::Exact(values) => values.push(value),
+            Estimator::Histogram(histogram) => histogram.push(value),
+            Estimator::P2(quantiles) => {
+                for quantile in quantiles {
+                    quantile.push(value, self.count);
+                }
+            }
+        }
+    }
+
+    /// Discards every observation, keeping the estimator and percentiles.
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.mean = 0.0;
+        self.m2 = 0.0;
+        self.min = f64::INFINITY;
+        self.max = f64::NEG_INFINITY;
+        self.batches.clear();
+        self.batch_size = 1;
+        match &mut self.estimator {
+            Estimator::Exact(values) => values.clear(),
+            Estimator::Histogram(histogram) => {
+                histogram.counts.fill(0);
+                histogram.below = 0;
+                histogram.above = 0;
+            }
+            Estimator::P2(quantiles) => {
+                for quantile in quantiles {
+                    *quantile = P2Quantile::new(quantile.probability);
+                }
+            }
+        }
+    }
+
+    /// Number of observations recorded.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether nothing was observed.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Observations in the order they were recorded, only kept by
+    /// [`Samples::exact`].
+    pub fn values(&self) -> Option<&[f64]> {
+        match &self.estimator {
+            Estimator::Exact(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Means of consecutive equal batches of observations, in the order they
+    /// were recorded, and the number of observations per batch. The batch
+    /// still being filled is left out.
+    pub fn batch_means(&self) -> (Vec<f64>, usize) {
+        let means = self
+            .batches
+            .iter()
+            .take(self.count / self.batch_size)
+            .map(|sum| sum / self.batch_size as f64)
+            .collect();
+        (means, self.batch_size)
+    }
+}
+
+impl Stats for Samples {
+    type Output = SampleStats;
+
+    fn stats(&self) -> SampleStats {
+        let empty = self.count == 0;
+        let percentiles: Vec<f64> = match &self.estimator {
+            Estimator::Exact(values) => {
+                let mut sorted = values.clone();
+                sorted.sort_by(f64::total_cmp);
+                self.percentiles
+                    .iter()
+                    .map(|&probability| percentile(&sorted, probability))
+                    .collect()
+            }
+            Estimator::Histogram(histogram) => self
+                .percentiles
+                .iter()
+                .map(|&probability| histogram.percentile(probability, self.min, self.max))
+                .collect(),
+            Estimator::P2(quantiles) => quantiles
+                .iter()
+                .map(|quantile| quantile.estimate(self.count))
+                .collect(),
+        };
+        SampleStats {
+            count: self.count,
+            mean: if empty { f64::NAN } else { self.mean },
+            variance: if self.count > 1 {
+                self.m2 / (self.count - 1) as f64
+            } else {
+                f64::NAN
+            },
+            min: if empty { f64::NAN } else { self.min },
+            max: if empty { f64::NAN } else { self.max },
+            percentiles: self
+                .percentiles
+                .iter()
+                .zip(percentiles)
+                .map(|(&probability, value)| Percentile { probability, value })
+                .collect(),
+            histogram: match &self.estimator {
+                Estimator::Histogram(histogram) => Some(histogram.buckets()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Histogram {
+    fn push(&mut self, value: f64) {
+        let bucket = (value - self.min) / self.width;
+        if bucket < 0.0 {
+            self.below += 1;
+        } else if bucket >= self.counts.len() as f64 {
+            self.above += 1;
+        } else {
+            self.counts[bucket as usize] += 1;
+        }
+    }
+
+    /// Every bucket including the open-ended ones below and above, each
+    /// with the fraction of observations in it.
+    fn buckets(&self) -> Vec<Bucket> {
+        let total = (self.below + self.above + self.counts.iter().sum::<usize>()) as f64;
+        let upper = self.min + self.width * self.counts.len() as f64;
+        let mut buckets = vec![Bucket {
+            lower: f64::NEG_INFINITY,
+            upper: self.min,
+            fraction: self.below as f64 / total,
+        }];
+        buckets.extend(self.counts.iter().enumerate().map(|(i, &count)| Bucket {
+            lower: self.min + self.width * i as f64,
+            upper: self.min + self.width * (i + 1) as f64,
+            fraction: count as f64 / total,
+        }));
+        buckets.push(Bucket {
+            lower: upper,
+            upper: f64::INFINITY,
+            fraction: self.above as f64 / total,
+        });
+        buckets
+    }
+
+    /// Percentile interpolated linearly within its bucket. The open-ended
+    /// buckets are bounded by the smallest and largest observations.
+    fn percentile(&self, probability: f64, min: f64, max: f64) -> f64 {
+        let total = self.below + self.above + self.counts.iter().sum::<usize>();
+        if total == 0 {
+            return f64::NAN;
+        }
+        let rank = probability.clamp(0.0, 1.0) * total as f64;
+        let upper = self.min + self.width * self.counts.len() as f64;
+        let buckets = std::iter::once((min, self.min, self.below))
+            .chain(self.counts.iter().enumerate().map(|(i, &count)| {
+                (
+                    self.min + self.width * i as f64,
+                    self.min + self.width * (i + 1) as f64,
+                    count,
+                )
+            }))
+            .chain(std::iter::once((upper, max, self.above)));
+        let mut cumulative = 0.0;
+        for (lower, upper, count) in buckets {
+            let count = count as f64;
+            if count > 0.0 && cumulative + count >= rank {
+                let (lower, upper) = (lower.max(min), upper.min(max));
+                return lower + (upper - lower) * (rank - cumulative) / count;
+            }
+            cumulative += count;
+        }
+        max
+    }
+}
+
+impl P2Quantile {
+    fn new(probability: f64) -> Self {
+        Self {
+            probability,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [
+                1.0,
+                1.0 + 2.0 * probability,
+                1.0 + 4.0 * probability,
+                3.0 + 2.0 * probability,
+                5.0,
+            ],
+            increments: [
+                0.0,
+                probability / 2.0,
+                probability,
+                (1.0 + probability) / 2.0,
+                1.0,
+            ],
+        }
+    }
+
+    /// Adds the `count`-th observation.
+    fn push(&mut self, value: f64, count: usize) {
+        if count <= 5 {
+            self.heights[count - 1] = value;
+            if count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5)
+                .find(|&i| value < self.heights[i])
+                .map_or(3, |i| i - 1)
+        };
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+            if (offset >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (offset <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let step = offset.signum();
+                let height = self.parabolic(i, step);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, step)
+                };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + step / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + step) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - step) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let j = if step > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i]
+            + step * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+
+    /// Estimate after `count` observations, exact while there are at most
+    /// five of them.
+    fn estimate(&self, count: usize) -> f64 {
+        if count > 5 {
+            return self.heights[2];
+        }
+        let mut sorted = self.heights[..count].to_vec();
+        sorted.sort_by(f64::total_cmp);
+        percentile(&sorted, self.probability)
+    }
+}
+
+impl LevelDistribution {
+    /// Starts at `level` at time `since`.
+    pub fn new(level: usize, since: Duration) -> Self {
+        Self {
+            times: Vec::new(),
+            level,
+            since,
+            percentiles: PERCENTILES.to_vec(),
+        }
+    }
+
+    /// Sets the reported percentiles, as probabilities.
+    pub fn percentiles(mut self, percentiles: &[f64]) -> Self {
+        self.percentiles = percentiles.to_vec();
+        self
+    }
+
+    /// Records a change to `level` at `time`.
+    pub fn set(&mut self, level: usize, time: Duration) {
+        if self.times.len() <= self.level {
+            self.times.resize(self.level + 1, 0.0);
+        }
+        self.times[self.level] += (time - self.since).as_secs_f64();
+        self.level = level;
+        self.since = time;
+    }
+
+    /// Extends the current level until `time` without recording a change.
+    /// Earlier times are ignored.
+    pub fn advance_to(&mut self, time: Duration) {
+        if time > self.since {
+            self.set(self.level, time);
+        }
+    }
+
+    /// Discards the time spent so far, restarting from the current level at
+    /// `time`.
+    pub fn reset(&mut self, time: Duration) {
+        self.times.clear();
+        self.since = time;
+    }
+}
+
+impl Stats for LevelDistribution {
+    type Output = LevelStats;
+
+    fn stats(&self) -> LevelStats {
+        let total = self.times.iter().sum::<f64>();
+        let distribution = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(level, &time)| Bucket {
+                lower: level as f64,
+                upper: (level + 1) as f64,
+                fraction: time / total,
+            })
+            .collect::<Vec<_>>();
+        let percentiles = self
+            .percentiles
+            .iter()
+            .map(|&probability| {
+                let mut cumulative = 0.0;
+                let value = distribution
+                    .iter()
+                    .find(|bucket| {
+                        cumulative += bucket.fraction;
+                        cumulative >= probability
+                    })
+                    .map_or(f64::NAN, |bucket| bucket.lower);
+                Percentile { probability, value }
+            })
+            .collect();
+        LevelStats {
+            percentiles,
+            distribution,
+        }
+    }
+}
+
+impl Metrics for SampleStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        let mut metrics = vec![
+            ("count".into(), self.count as f64),
+            ("mean".into(), self.mean),
+            ("variance".into(), self.variance),
+            ("min".into(), self.min),
+            ("max".into(), self.max),
+        ];
+        metrics.extend(
+            self.percentiles
+                .iter()
+                .map(|percentile| (percentile.name(), percentile.value)),
+        );
+        metrics
+    }
+}
+
+impl Metrics for LevelStats {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        self.percentiles
+            .iter()
+            .map(|percentile| (percentile.name(), percentile.value))
+            .collect()
+    }
+}
+
+impl Percentile {
+    /// Metric name of the percentile, e.g. `p95` or `p99.9`.
+    pub fn name(&self) -> String {
+        format!("p{}", self.probability * 100.0)
+    }
+}
+
+/// Percentile of `sorted` values at `probability`, interpolating linearly
+/// between the closest ranks. NaN without values.
+pub fn percentile(sorted: &[f64], probability: f64) -> f64 {
+    let Some(&last) = sorted.last() else {
+        return f64::NAN;
+    };
+    let rank = probability.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    match sorted.get(lower + 1) {
+        Some(&upper) => sorted[lower] + (upper - sorted[lower]) * (rank - lower as f64),
+        None => last,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Observations of the worked example of Jain and Chlamtac.
+    const JAIN_CHLAMTAC: [f64; 20] = [
+        0.02, 0.5, 0.74, 3.39, 0.83, 22.37, 10.15, 15.43, 38.62, 15.92, 34.6, 10.28, 1.47, 0.4,
+        0.05, 11.39, 0.27, 0.42, 0.09, 11.37,
+    ];
+
+    #[test]
+    fn batches_are_merged_pairwise_when_full() {
+        let mut samples = Samples::p2();
+        for value in 0..5000 {
+            samples.push(f64::from(value));
+        }
+        let (means, batch_size) = samples.batch_means();
+        assert_eq!(batch_size, 4);
+        assert_eq!(means.len(), 1250);
+        assert_eq!(means[0], 1.5);
+        assert_eq!(means[1249], 4997.5);
+
+        samples.clear();
+        samples.push(1.0);
+        assert_eq!(samples.batch_means(), (vec![1.0], 1));
+    }
+
+    #[test]
+    fn p2_markers_follow_the_worked_example() {
+        let mut quantile = P2Quantile::new(0.5);
+        for (i, &value) in JAIN_CHLAMTAC.iter().enumerate() {
+            quantile.push(value, i + 1);
+        }
+        assert_eq!(quantile.positions, [1.0, 6.0, 10.0, 16.0, 20.0]);
+        let expected = [0.02, 0.154, 4.247, 17.556, 38.62];
+        for (height, expected) in quantile.heights.iter().zip(expected) {
+            assert!((height - expected).abs() < 0.001, "{height} != {expected}");
+        }
+        assert_eq!(quantile.estimate(JAIN_CHLAMTAC.len()), quantile.heights[2]);
+    }
+
+    #[test]
+    fn p2_estimates_match_exact_percentiles() {
+        let mut exact = Samples::exact();
+        let mut p2 = Samples::p2();
+        // Every value below 10007 once, in a scrambled order.
+        for i in 0..10007_u64 {
+            let value = (i * 7919 % 10007) as f64;
+            exact.push(value);
+            p2.push(value);
+        }
+        let exact = exact.stats().percentiles;
+        let p2 = p2.stats().percentiles;
+        for (exact, p2) in exact.iter().zip(&p2) {
+            assert!(
+                (exact.value - p2.value).abs() < 100.0,
+                "p{}: {} != {}",
+                exact.probability,
+                p2.value,
+                exact.value
+            );
+        }
+    }
+
+    #[test]
+    fn p2_is_exact_with_five_observations() {
+        let mut samples = Samples::p2().percentiles(&[0.5]);
+        for value in [5.0, 1.0, 4.0, 2.0, 3.0] {
+            samples.push(value);
+        }
+        assert_eq!(samples.stats().percentiles[0].value, 3.0);
+    }
+
+    #[test]
+    fn histogram_percentiles_match_exact_ones() {
+        let probabilities = [0.1, 0.25, 0.5, 0.9, 0.99];
+        let mut exact = Samples::exact().percentiles(&probabilities);
+        let mut histogram = Samples::histogram(0.0, 1.0, 100).percentiles(&probabilities);
+        for i in 0..1000 {
+            let value = (i as f64 + 0.5) / 10.0;
+            exact.push(value);
+            histogram.push(value);
+        }
+        let exact = exact.stats().percentiles;
+        let histogram = histogram.stats().percentiles;
+        for (exact, histogram) in exact.iter().zip(&histogram) {
+            assert!(
+                (exact.value - histogram.value).abs() < 0.1,
+                "p{}: {} != {}",
+                exact.probability,
+                histogram.value,
+                exact.value
+            );
+        }
+    }
+
+    #[test]
+    fn histogram_percentiles_are_bounded_by_observations() {
+        let mut samples = Samples::histogram(0.0, 10.0, 2).percentiles(&[0.0, 0.5, 1.0]);
+        for value in [-4.0, 2.0, 12.0, 30.0] {
+            samples.push(value);
+        }
+        let values: Vec<_> = samples
+            .stats()
+            .percentiles
+            .iter()
+            .map(|percentile| percentile.value)
+            .collect();
+        assert_eq!(values, [-4.0, 10.0, 30.0]);
+    }
+
+    #[test]
+    fn nan_observations_are_ignored() {
+        let mut samples = Samples::p2();
+        for value in [1.0, 2.0, 3.0, 4.0, 5.0, f64::NAN, 6.0] {
+            samples.push(value);
+        }
+        let stats = samples.stats();
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.mean, 3.5);
+        assert!(stats
+            .percentiles
+            .iter()
+            .all(|percentile| !percentile.value.is_nan()));
+    }
+}
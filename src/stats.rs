@@ -1,9 +1,40 @@
-use std::fmt::Debug;
+//! Traits through which blocks report their statistics.
 
+/// Statistics collected over the whole simulation.
 pub trait Stats {
-    fn stats(&self) -> Box<dyn Debug>;
+    /// Summary of the statistics.
+    type Output;
+    /// Summarizes the statistics collected so far.
+    fn stats(&self) -> Self::Output;
 }
 
+/// Instantaneous state, reported after every simulation step.
 pub trait StepStats {
-    fn step_stats(&self) -> Box<dyn Debug>;
+    /// Snapshot of the state.
+    type Output;
+    /// Snapshot of the current state.
+    fn step_stats(&self) -> Self::Output;
+}
+
+/// Flat view of statistics as named values, for tabular output and
+/// aggregation across runs. Nested statistics are prefixed with the name of
+/// their field, e.g. `queue.average_length`.
+pub trait Metrics {
+    /// Every named value, in a stable order.
+    fn metrics(&self) -> Vec<(String, f64)>;
+}
+
+impl<T: Metrics> Metrics for Option<T> {
+    fn metrics(&self) -> Vec<(String, f64)> {
+        self.as_ref().map(T::metrics).unwrap_or_default()
+    }
+}
+
+/// Metrics of `stats`, each prefixed with `prefix.`.
+pub fn prefixed(prefix: &str, stats: &impl Metrics) -> Vec<(String, f64)> {
+    stats
+        .metrics()
+        .into_iter()
+        .map(|(name, value)| (format!("{prefix}.{name}"), value))
+        .collect()
 }
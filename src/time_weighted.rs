@@ -0,0 +1,194 @@
+//! Time-weighted statistics of quantities that change at discrete
+//! times.
+
+use crate::stats::Stats;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Width of the batches a run is first split into, in seconds.
+const INITIAL_BATCH_WIDTH: f64 = 0.001;
+
+/// Most batches kept at once. When a run outgrows them, adjacent batches are
+/// merged pairwise and their width doubles.
+const MAX_BATCHES: usize = 2048;
+
+/// Time-weighted statistics of a piecewise-constant quantity, e.g. a queue
+/// length, updated on every change in constant memory.
+///
+/// Besides the area under the quantity, the accumulator keeps the areas of at
+/// most 2048 equal time batches for batch-means estimates. The full
+/// series of changes is only recorded after
+/// [`TimeWeighted::record_history`].
+#[derive(Debug, Clone)]
+pub struct TimeWeighted {
+    start: Duration,
+    last_change: Duration,
+    value: f64,
+    area: f64,
+    square_area: f64,
+    min: f64,
+    max: f64,
+    batches: Vec<f64>,
+    batch_width: f64,
+    history: Option<Vec<(Duration, f64)>>,
+}
+
+/// Summary of a [`TimeWeighted`] quantity until its last change.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeWeightedStats {
+    /// Time-weighted average, or the current value if no time has passed.
+    pub average: f64,
+    /// Time-weighted variance, zero if no time has passed.
+    pub variance: f64,
+    /// Lowest value taken.
+    pub min: f64,
+    /// Highest value taken.
+    pub max: f64,
+}
+
+impl TimeWeighted {
+    /// Starts observing `value` at `start`.
+    pub fn new(value: f64, start: Duration) -> Self {
+        Self {
+            start,
+            last_change: start,
+            value,
+            area: 0.0,
+            square_area: 0.0,
+            min: value,
+            max: value,
+            batches: Vec::new(),
+            batch_width: INITIAL_BATCH_WIDTH,
+            history: None,
+        }
+    }
+
+    /// Records every change from now on, starting with the current value.
+    pub fn record_history(mut self) -> Self {
+        self.history = Some(vec![(self.last_change, self.value)]);
+        self
+    }
+
+    /// Every change recorded since the last reset, if recording.
+    pub fn history(&self) -> Option<&[(Duration, f64)]> {
+        self.history.as_deref()
+    }
+
+    /// Current value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Records a change to `value` at `time`.
+    pub fn set(&mut self, value: f64, time: Duration) {
+        self.advance(time);
+        self.value = value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if let Some(history) = &mut self.history {
+            history.push((time, value));
+        }
+    }
+
+    /// Discards everything observed so far, restarting from the current value
+    /// at `time`.
+    pub fn reset(&mut self, time: Duration) {
+        let history = self.history.is_some();
+        *self = Self::new(self.value, time);
+        if history {
+            self.history = Some(vec![(time, self.value)]);
+        }
+    }
+
+    /// Integral of the quantity over time until its last change.
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    /// Time observed until the last change.
+    pub fn duration(&self) -> Duration {
+        self.last_change - self.start
+    }
+
+    /// Time-weighted averages of consecutive equal batches of time from the
+    /// start until `end`, and the width of the batches in seconds. The batch
+    /// still in progress at `end` is left out.
+    pub fn batch_means(&self, end: Duration) -> (Vec<f64>, f64) {
+        let mut observed = self.clone();
+        observed.advance_to(end);
+        let complete =
+            ((end.saturating_sub(observed.start)).as_secs_f64() / observed.batch_width) as usize;
+        let means = observed
+            .batches
+            .iter()
+            .take(complete)
+            .map(|area| area / observed.batch_width)
+            .collect();
+        (means, observed.batch_width)
+    }
+
+    /// Extends the current value until `time` without recording a change, so
+    /// the statistics cover time since the last change. Earlier times are
+    /// ignored.
+    pub fn advance_to(&mut self, time: Duration) {
+        if time > self.last_change {
+            self.advance(time);
+        }
+    }
+
+    /// Adds the area under the current value until `time`.
+    fn advance(&mut self, time: Duration) {
+        let mut from = (self.last_change - self.start).as_secs_f64();
+        let to = (time - self.start).as_secs_f64();
+        self.area += self.value * (to - from);
+        self.square_area += self.value * self.value * (to - from);
+        self.last_change = time;
+
+        let mut batch = (from / self.batch_width) as usize;
+        loop {
+            if batch >= MAX_BATCHES {
+                self.batches = self
+                    .batches
+                    .chunks(2)
+                    .map(|pair| pair.iter().sum())
+                    .collect();
+                self.batch_width *= 2.0;
+                batch /= 2;
+                continue;
+            }
+            let end = ((batch + 1) as f64 * self.batch_width).min(to);
+            if self.batches.len() <= batch {
+                self.batches.resize(batch + 1, 0.0);
+            }
+            self.batches[batch] += self.value * (end - from).max(0.0);
+            if end >= to {
+                break;
+            }
+            from = end;
+            batch += 1;
+        }
+    }
+}
+
+impl Stats for TimeWeighted {
+    type Output = TimeWeightedStats;
+
+    fn stats(&self) -> TimeWeightedStats {
+        let duration = self.duration().as_secs_f64();
+        let (average, variance) = if duration > 0.0 {
+            let average = self.area / duration;
+            (
+                average,
+                (self.square_area / duration - average * average).max(0.0),
+            )
+        } else {
+            (self.value, 0.0)
+        };
+        TimeWeightedStats {
+            average,
+            variance,
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use queue_network::{
+    blocks::{BlockStats, CreateBlock, DisposeBlock, ProcessBlock},
+    devices::Devices,
+    network::QueueNetwork,
+    queue::Queue,
+    routers::{DirectRouter, ProbabilityRouter},
+};
+use rand_distr::Exp;
+use std::time::Duration;
+
+fn network() -> QueueNetwork {
+    QueueNetwork::new()
+        .seed(42)
+        .add_block(
+            CreateBlock::builder("create")
+                .distribution(Exp::new(0.5).unwrap())
+                .router(DirectRouter::new("process1"))
+                .build(),
+        )
+        .add_block(
+            ProcessBlock::builder("process1")
+                .distribution(Exp::new(1.0).unwrap())
+                .queue(Queue::from_capacity(5))
+                .router(DirectRouter::new("process2"))
+                .build(),
+        )
+        .add_block(
+            ProcessBlock::builder("process2")
+                .distribution(Exp::new(1.0).unwrap())
+                .queue(Queue::from_capacity(5))
+                .devices(Devices::new(2))
+                .router(ProbabilityRouter::new(&[
+                    (0.5, "process1"),
+                    (0.5, "dispose"),
+                ]))
+                .build(),
+        )
+        .add_block(DisposeBlock::new("dispose"))
+}
+
+#[test]
+fn fixed_seed_reproduces_exact_stats() {
+    let mut network = network();
+    network.simulate(Duration::from_secs(1000)).unwrap();
+    let stats = network.stats();
+
+    let BlockStats::Create(create) = &stats["create"] else {
+        panic!("create is not a create block");
+    };
+    let BlockStats::Process(process1) = &stats["process1"] else {
+        panic!("process1 is not a process block");
+    };
+    let BlockStats::Process(process2) = &stats["process2"] else {
+        panic!("process2 is not a process block");
+    };
+    let BlockStats::Dispose(dispose) = &stats["dispose"] else {
+        panic!("dispose is not a dispose block");
+    };
+    assert_eq!(create.created_events, 508);
+    assert_eq!(process1.processed, 888);
+    assert_eq!(process1.rejections, 64);
+    assert_eq!(process1.devices.average_workload, 0.8639881);
+    assert_eq!(process1.wait_time.mean, 2.192510260930261);
+    assert_eq!(process2.processed, 887);
+    assert_eq!(process2.service_time.mean, 0.9865004777736495);
+    assert_eq!(dispose.disposed_events, 440);
+    assert_eq!(dispose.system_time.mean, 8.28682851782272);
+}
+
+#[test]
+fn same_seed_gives_same_run() {
+    let mut first = network();
+    let mut second = network();
+    first.simulate(Duration::from_secs(500)).unwrap();
+    second.simulate(Duration::from_secs(500)).unwrap();
+    assert_eq!(
+        format!("{:?}", first.stats()),
+        format!("{:?}", second.stats())
+    );
+}